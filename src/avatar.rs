@@ -1,70 +1,175 @@
+use crate::travel::{is_sailing, TravelCosts};
 use crate::world::World;
 use isometric::coords::*;
 use isometric::drawing::Billboard;
 use isometric::Command;
 use isometric::Texture;
-use isometric::{v3, V3};
+use isometric::{v2, v3, V2, V3};
+use std::collections::VecDeque;
 use std::f32::consts::PI;
 use std::sync::Arc;
 
+#[derive(PartialEq, Debug, Clone, Copy)]
 enum Rotation {
     Left,
+    UpLeft,
     Up,
+    UpRight,
     Right,
+    DownRight,
     Down,
+    DownLeft,
 }
 
 impl Rotation {
     fn clockwise(&self) -> Rotation {
         match self {
-            Rotation::Left => Rotation::Up,
-            Rotation::Up => Rotation::Right,
-            Rotation::Right => Rotation::Down,
-            Rotation::Down => Rotation::Left,
+            Rotation::Left => Rotation::UpLeft,
+            Rotation::UpLeft => Rotation::Up,
+            Rotation::Up => Rotation::UpRight,
+            Rotation::UpRight => Rotation::Right,
+            Rotation::Right => Rotation::DownRight,
+            Rotation::DownRight => Rotation::Down,
+            Rotation::Down => Rotation::DownLeft,
+            Rotation::DownLeft => Rotation::Left,
         }
     }
 
     fn anticlockwise(&self) -> Rotation {
         match self {
-            Rotation::Left => Rotation::Down,
-            Rotation::Up => Rotation::Left,
-            Rotation::Right => Rotation::Up,
-            Rotation::Down => Rotation::Right,
+            Rotation::Left => Rotation::DownLeft,
+            Rotation::UpLeft => Rotation::Left,
+            Rotation::Up => Rotation::UpLeft,
+            Rotation::UpRight => Rotation::Up,
+            Rotation::Right => Rotation::UpRight,
+            Rotation::DownRight => Rotation::Right,
+            Rotation::Down => Rotation::DownRight,
+            Rotation::DownLeft => Rotation::Down,
         }
     }
 
     fn angle(&self) -> f32 {
         match self {
             Rotation::Left => 0.0 * (PI / 4.0),
+            Rotation::UpLeft => 1.0 * (PI / 4.0),
             Rotation::Up => 2.0 * (PI / 4.0),
+            Rotation::UpRight => 3.0 * (PI / 4.0),
             Rotation::Right => 4.0 * (PI / 4.0),
+            Rotation::DownRight => 5.0 * (PI / 4.0),
             Rotation::Down => 6.0 * (PI / 4.0),
+            Rotation::DownLeft => 7.0 * (PI / 4.0),
         }
     }
+
+    fn offset(&self) -> (i64, i64) {
+        let angle = self.angle();
+        (angle.cos().round() as i64, angle.sin().round() as i64)
+    }
+
+    fn towards(from: &V2<usize>, to: &V2<usize>) -> Option<Rotation> {
+        let offset = (
+            (to.x as i64 - from.x as i64).signum(),
+            (to.y as i64 - from.y as i64).signum(),
+        );
+        let mut rotation = Rotation::Left;
+        for _ in 0..8 {
+            if rotation.offset() == offset {
+                return Some(rotation);
+            }
+            rotation = rotation.clockwise();
+        }
+        None
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Order {
+    Walk,
+    BuildRoad,
+    GoTo(V2<usize>),
+}
+
+#[derive(Clone)]
+pub struct AvatarTextures {
+    body: Arc<Texture>,
+    head: Arc<Texture>,
+    eye: Arc<Texture>,
+    hand: Arc<Texture>,
+    boat: Arc<Texture>,
+}
+
+impl AvatarTextures {
+    pub fn load() -> AvatarTextures {
+        AvatarTextures {
+            body: Arc::new(Texture::new(image::open("body.png").unwrap())),
+            head: Arc::new(Texture::new(image::open("head.png").unwrap())),
+            eye: Arc::new(Texture::new(image::open("eye.png").unwrap())),
+            hand: Arc::new(Texture::new(image::open("hand.png").unwrap())),
+            boat: Arc::new(Texture::new(image::open("boat.png").unwrap())),
+        }
+    }
+}
+
+struct Movement {
+    from: WorldCoord,
+    to: WorldCoord,
+    duration: u64,
+    elapsed: u64,
+    floating: bool,
+}
+
+impl Movement {
+    fn progress(&self) -> f32 {
+        (self.elapsed as f32 / self.duration as f32).min(1.0)
+    }
+
+    fn arrived(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn position(&self, world: &World) -> WorldCoord {
+        let t = self.progress();
+        let x = self.from.x + (self.to.x - self.from.x) * t;
+        let y = self.from.y + (self.to.y - self.from.y) * t;
+        let z = world
+            .get_surface_elevation(x, y)
+            .unwrap_or(self.from.z + (self.to.z - self.from.z) * t);
+        if self.floating {
+            WorldCoord::new(x, y, z.max(world.sea_level()))
+        } else {
+            WorldCoord::new(x, y, z)
+        }
+    }
+}
+
+fn to_ticks(cost: f32) -> u64 {
+    cost.ceil().max(1.0) as u64
 }
 
 pub struct Avatar {
+    name: String,
     scale: f32,
-    max_grade: f32,
+    costs: TravelCosts,
     rotation: Rotation,
     position: Option<WorldCoord>,
-    texture_body: Arc<Texture>,
-    texture_head: Arc<Texture>,
-    texture_eye: Arc<Texture>,
-    texture_hand: Arc<Texture>,
+    movement: Option<Movement>,
+    boat: bool,
+    orders: VecDeque<Order>,
+    textures: AvatarTextures,
 }
 
 impl Avatar {
-    pub fn new(scale: f32, max_grade: f32) -> Avatar {
+    pub fn new(name: String, scale: f32, costs: TravelCosts, textures: AvatarTextures) -> Avatar {
         Avatar {
-            max_grade,
+            name,
+            costs,
             scale,
             rotation: Rotation::Up,
             position: None,
-            texture_body: Arc::new(Texture::new(image::open("body.png").unwrap())),
-            texture_head: Arc::new(Texture::new(image::open("head.png").unwrap())),
-            texture_eye: Arc::new(Texture::new(image::open("eye.png").unwrap())),
-            texture_hand: Arc::new(Texture::new(image::open("hand.png").unwrap())),
+            movement: None,
+            boat: false,
+            orders: VecDeque::new(),
+            textures,
         }
     }
 
@@ -72,6 +177,46 @@ impl Avatar {
         self.position
     }
 
+    pub fn display_position(&self, world: &World) -> Option<WorldCoord> {
+        match self.movement {
+            Some(ref movement) => Some(movement.position(world)),
+            None => self.position,
+        }
+    }
+
+    pub fn set_costs(&mut self, costs: TravelCosts) {
+        self.costs = costs;
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.movement.is_some()
+    }
+
+    pub fn give_order(&mut self, order: Order) {
+        self.orders.push_back(order);
+    }
+
+    pub fn resume_order(&mut self, order: Order) {
+        self.orders.push_front(order);
+    }
+
+    pub fn next_order(&mut self) -> Option<Order> {
+        if self.is_moving() {
+            None
+        } else {
+            self.orders.pop_front()
+        }
+    }
+
+    pub fn face(&mut self, target: &V2<usize>) {
+        if let Some(position) = self.position {
+            let position = v2(position.x as usize, position.y as usize);
+            if let Some(rotation) = Rotation::towards(&position, target) {
+                self.rotation = rotation;
+            }
+        }
+    }
+
     pub fn rotate_clockwise(&mut self) {
         self.rotation = self.rotation.clockwise();
     }
@@ -83,23 +228,78 @@ impl Avatar {
     pub fn reposition(&mut self, world_coord: Option<WorldCoord>, world: &World) {
         if let Some(world_coord) = world_coord {
             self.position = Some(world.snap(world_coord));
+            self.movement = None;
+            self.boat = false;
+            self.orders.clear();
         }
     }
 
     pub fn walk(&mut self, world: &World) {
+        if self.movement.is_some() {
+            return;
+        }
         if let Some(position) = self.position {
-            let new_position = WorldCoord::new(
-                position.x + self.rotation.angle().cos(),
-                position.y + self.rotation.angle().sin(),
-                0.0,
-            );
-            let new_position = world.snap(new_position);
-            if (new_position.z - position.z).abs() < self.max_grade {
-                self.position = Some(new_position);
+            let (dx, dy) = self.rotation.offset();
+            let x = position.x as i64 + dx;
+            let y = position.y as i64 + dy;
+            if x < 0 || y < 0 || !world.in_bounds(&v2(x as usize, y as usize)) {
+                return;
+            }
+            let from = v2(position.x as usize, position.y as usize);
+            let to = v2(x as usize, y as usize);
+            if let Some(cost) = self.costs.get_cost(world, &from, &to) {
+                self.move_to(world, position, to, cost, is_sailing(world, &from, &to));
             }
         }
     }
 
+    fn move_to(
+        &mut self,
+        world: &World,
+        position: WorldCoord,
+        to: V2<usize>,
+        cost: f32,
+        sailing: bool,
+    ) {
+        let new_position = world.snap(WorldCoord::new(to.x as f32, to.y as f32, 0.0));
+        let new_position = if sailing {
+            let z = new_position.z.max(world.sea_level());
+            WorldCoord::new(new_position.x, new_position.y, z)
+        } else {
+            new_position
+        };
+        self.boat = sailing;
+        self.movement = Some(Movement {
+            from: position,
+            to: new_position,
+            duration: to_ticks(cost),
+            elapsed: 0,
+            floating: sailing,
+        });
+        self.position = Some(new_position);
+    }
+
+    pub fn tick(&mut self, world: &World) -> Vec<Command> {
+        let arrived = match self.movement {
+            Some(ref mut movement) => {
+                movement.elapsed += 1;
+                movement.arrived()
+            }
+            None => return vec![],
+        };
+        let commands = self.draw(world);
+        if arrived {
+            self.movement = None;
+        }
+        commands
+    }
+
+    fn get_walk_phase(&self) -> f32 {
+        self.movement
+            .as_ref()
+            .map_or(0.0, |movement| (movement.progress() * 2.0 * PI).sin())
+    }
+
     #[rustfmt::skip]
     pub fn get_rotation_matrix(&self) -> na::Matrix3<f32> {
         let cos = self.rotation.angle().cos();
@@ -115,7 +315,7 @@ impl Avatar {
         &self,
         position: WorldCoord,
         offset: V3<f32>,
-        handle: &str,
+        part: &str,
         texture: &Arc<Texture>,
     ) -> Command {
         let offset = self.get_rotation_matrix() * offset * self.scale;
@@ -127,54 +327,134 @@ impl Avatar {
         let width = (texture.width() as f32) * self.scale;
         let height = (texture.height() as f32) * self.scale;
         Command::Draw {
-            name: handle.to_string(),
+            name: format!("{}-{}", self.name, part),
             drawing: Box::new(Billboard::new(position, width, height, texture.clone())),
         }
     }
 
-    pub fn draw(&self) -> Vec<Command> {
-        if let Some(position) = self.position {
+    fn draw_boat(&self, position: WorldCoord) -> Command {
+        if self.boat {
+            self.draw_billboard_at_offset(position, v3(0.0, 0.0, 16.0), "boat", &self.textures.boat)
+        } else {
+            Command::Erase(format!("{}-boat", self.name))
+        }
+    }
+
+    pub fn draw(&self, world: &World) -> Vec<Command> {
+        if let Some(position) = self.display_position(world) {
+            let swing = self.get_walk_phase() * 16.0;
+            let bob = self.get_walk_phase().abs() * 4.0;
             vec![
                 self.draw_billboard_at_offset(
                     position,
                     v3(0.0, 0.0, 96.0),
                     "body",
-                    &self.texture_body,
+                    &self.textures.body,
                 ),
                 self.draw_billboard_at_offset(
                     position,
                     v3(12.0, 0.0, 192.0),
                     "head",
-                    &self.texture_head,
+                    &self.textures.head,
                 ),
                 self.draw_billboard_at_offset(
                     position,
-                    v3(48.0, 24.0, 192.0),
+                    v3(48.0, 24.0, 192.0 + bob),
                     "left_eye",
-                    &self.texture_eye,
+                    &self.textures.eye,
                 ),
                 self.draw_billboard_at_offset(
                     position,
-                    v3(48.0, -24.0, 192.0),
+                    v3(48.0, -24.0, 192.0 + bob),
                     "right_eye",
-                    &self.texture_eye,
+                    &self.textures.eye,
                 ),
                 self.draw_billboard_at_offset(
                     position,
-                    v3(48.0, 50.0, 96.0),
+                    v3(48.0 + swing, 50.0, 96.0),
                     "left_hand",
-                    &self.texture_hand,
+                    &self.textures.hand,
                 ),
                 self.draw_billboard_at_offset(
                     position,
-                    v3(48.0, -50.0, 96.0),
+                    v3(48.0 - swing, -50.0, 96.0),
                     "right_hand",
-                    &self.texture_hand,
+                    &self.textures.hand,
                 ),
-                Command::LookAt(position),
+                self.draw_boat(position),
             ]
         } else {
             vec![]
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_eight_rotations() {
+        let mut rotation = Rotation::Left;
+        for _ in 0..8 {
+            let next = rotation.clockwise();
+            assert_eq!(next.anticlockwise(), rotation);
+            rotation = next;
+        }
+        assert_eq!(rotation, Rotation::Left);
+    }
+
+    #[test]
+    fn test_offsets() {
+        assert_eq!(Rotation::Left.offset(), (1, 0));
+        assert_eq!(Rotation::UpLeft.offset(), (1, 1));
+        assert_eq!(Rotation::Up.offset(), (0, 1));
+        assert_eq!(Rotation::UpRight.offset(), (-1, 1));
+        assert_eq!(Rotation::Right.offset(), (-1, 0));
+        assert_eq!(Rotation::DownRight.offset(), (-1, -1));
+        assert_eq!(Rotation::Down.offset(), (0, -1));
+        assert_eq!(Rotation::DownLeft.offset(), (1, -1));
+    }
+
+    #[test]
+    fn test_towards() {
+        assert_eq!(
+            Rotation::towards(&v2(2, 2), &v2(5, 2)),
+            Some(Rotation::Left)
+        );
+        assert_eq!(
+            Rotation::towards(&v2(2, 2), &v2(0, 0)),
+            Some(Rotation::DownRight)
+        );
+        assert_eq!(
+            Rotation::towards(&v2(2, 2), &v2(3, 7)),
+            Some(Rotation::UpLeft)
+        );
+        assert_eq!(Rotation::towards(&v2(2, 2), &v2(2, 2)), None);
+    }
+
+    #[test]
+    fn test_to_ticks() {
+        assert_eq!(to_ticks(10.0), 10);
+        assert_eq!(to_ticks(14.1), 15);
+        assert_eq!(to_ticks(0.2), 1);
+    }
+
+    #[test]
+    fn test_movement_progress() {
+        let mut movement = Movement {
+            from: WorldCoord::new(0.0, 0.0, 0.0),
+            to: WorldCoord::new(1.0, 0.0, 0.0),
+            duration: 4,
+            elapsed: 0,
+            floating: false,
+        };
+        assert_eq!(movement.progress(), 0.0);
+        movement.elapsed = 1;
+        assert_eq!(movement.progress(), 0.25);
+        assert!(!movement.arrived());
+        movement.elapsed = 4;
+        assert!(movement.arrived());
+    }
+}
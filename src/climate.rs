@@ -0,0 +1,297 @@
+use crate::sun::lerp;
+use crate::world::World;
+use isometric::*;
+use pioneer::rand::prelude::*;
+use std::f32::consts::PI;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub fn tint(&self) -> Color {
+        match self {
+            Season::Spring => Color::new(1.0, 1.0, 1.0, 1.0),
+            Season::Summer => Color::new(1.0, 1.0, 0.9, 1.0),
+            Season::Autumn => Color::new(1.0, 0.9, 0.8, 1.0),
+            Season::Winter => Color::new(0.85, 0.9, 1.0, 1.0),
+        }
+    }
+
+    pub fn vegetation(&self, grass: &Color) -> Color {
+        match self {
+            Season::Spring => *grass,
+            Season::Summer => lerp(grass, &Color::new(0.6, 0.7, 0.2, 1.0), 0.3),
+            Season::Autumn => lerp(grass, &Color::new(0.8, 0.5, 0.1, 1.0), 0.5),
+            Season::Winter => lerp(grass, &Color::new(0.5, 0.5, 0.4, 1.0), 0.5),
+        }
+    }
+
+    fn rain_chance(&self) -> f32 {
+        match self {
+            Season::Spring => 0.4,
+            Season::Summer => 0.2,
+            Season::Autumn => 0.4,
+            Season::Winter => 0.3,
+        }
+    }
+
+    fn growth(&self) -> f32 {
+        match self {
+            Season::Spring => 1.0,
+            Season::Summer => 1.2,
+            Season::Autumn => 0.8,
+            Season::Winter => 0.1,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Snow,
+}
+
+impl Weather {
+    pub fn travel_factor(&self) -> f32 {
+        match self {
+            Weather::Clear => 1.0,
+            Weather::Rain => 1.5,
+            Weather::Snow => 2.0,
+        }
+    }
+
+    fn growth(&self) -> f32 {
+        match self {
+            Weather::Clear => 1.0,
+            Weather::Rain => 1.25,
+            Weather::Snow => 0.5,
+        }
+    }
+}
+
+pub struct Climate {
+    year_length: u64,
+    ticks: u64,
+    weather: Weather,
+    rng: SmallRng,
+    snowline: f32,
+    snowline_swing: f32,
+    snowfall: f32,
+    melt: f32,
+    river_swell: f32,
+}
+
+impl Climate {
+    pub fn new(year_length: u64, seed: u8) -> Climate {
+        Climate {
+            year_length,
+            ticks: 0,
+            weather: Weather::Clear,
+            rng: SmallRng::from_seed([seed; 16]),
+            snowline: 0.7,
+            snowline_swing: 0.3,
+            snowfall: 0.1,
+            melt: 0.05,
+            river_swell: 1.5,
+        }
+    }
+
+    fn time_of_year(&self) -> f32 {
+        (self.ticks % self.year_length) as f32 / self.year_length as f32
+    }
+
+    pub fn season(&self) -> Season {
+        match (self.time_of_year() * 4.0) as u64 {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    pub fn weather(&self) -> Weather {
+        self.weather
+    }
+
+    pub fn tick(&mut self) -> bool {
+        let season = self.season();
+        self.ticks += 1;
+        season != self.season()
+    }
+
+    pub fn update_weather(&mut self) -> bool {
+        let weather = self.weather;
+        self.weather = self.roll_weather();
+        weather != self.weather
+    }
+
+    fn roll_weather(&mut self) -> Weather {
+        let season = self.season();
+        if self.rng.gen::<f32>() >= season.rain_chance() {
+            Weather::Clear
+        } else if season == Season::Winter {
+            Weather::Snow
+        } else {
+            Weather::Rain
+        }
+    }
+
+    pub fn snowline(&self, max_height: f32) -> f32 {
+        let phase = (self.time_of_year() - 0.375) * 2.0 * PI;
+        max_height * (self.snowline + self.snowline_swing * phase.cos())
+    }
+
+    pub fn river_swell(&self) -> f32 {
+        if self.weather == Weather::Rain {
+            self.river_swell
+        } else {
+            1.0
+        }
+    }
+
+    pub fn rainfall(&self) -> f32 {
+        match self.weather {
+            Weather::Clear => 0.1,
+            Weather::Rain => 1.0,
+            Weather::Snow => 0.5,
+        }
+    }
+
+    pub fn yield_factor(&self) -> f32 {
+        self.season().growth() * self.weather.growth()
+    }
+
+    pub fn update_snow(&self, world: &mut World) -> Vec<V2<usize>> {
+        let snowline = self.snowline(world.max_height());
+        let mut out = vec![];
+        for x in 0..world.width() - 1 {
+            for y in 0..world.height() - 1 {
+                let position = v2(x, y);
+                let before = world.get_snow(&position);
+                let after = if world.get_lowest_corner(&position) < snowline {
+                    (before - self.melt).max(0.0)
+                } else if self.weather == Weather::Snow {
+                    before + self.snowfall
+                } else {
+                    before
+                };
+                if after != before {
+                    world.set_snow(&position, after);
+                }
+                if (after > 0.0) != (before > 0.0) {
+                    out.push(position);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn almost_equal(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    #[test]
+    fn test_seasons() {
+        let mut climate = Climate::new(400, 0);
+        assert_eq!(climate.season(), Season::Spring);
+        for _ in 0..99 {
+            assert!(!climate.tick());
+        }
+        assert!(climate.tick());
+        assert_eq!(climate.season(), Season::Summer);
+        for _ in 0..200 {
+            climate.tick();
+        }
+        assert_eq!(climate.season(), Season::Winter);
+        for _ in 0..100 {
+            climate.tick();
+        }
+        assert_eq!(climate.season(), Season::Spring);
+    }
+
+    #[test]
+    fn test_snowline_is_highest_in_summer() {
+        let mut climate = Climate::new(400, 0);
+        let mut highest = (0, 0.0);
+        let mut lowest = (0, 1.0);
+        for tick in 0..400 {
+            let snowline = climate.snowline(1.0);
+            if snowline > highest.1 {
+                highest = (tick, snowline);
+            }
+            if snowline < lowest.1 {
+                lowest = (tick, snowline);
+            }
+            climate.tick();
+        }
+        assert_eq!(highest.0, 150);
+        assert!(almost_equal(highest.1, 1.0));
+        assert_eq!(lowest.0, 350);
+        assert!(almost_equal(lowest.1, 0.4));
+    }
+
+    #[test]
+    fn test_winter_precipitation_is_snow() {
+        let mut climate = Climate::new(400, 0);
+        for _ in 0..300 {
+            climate.tick();
+        }
+        let mut weathers = vec![];
+        for _ in 0..99 {
+            climate.tick();
+            climate.update_weather();
+            weathers.push(climate.weather());
+        }
+        assert!(weathers.contains(&Weather::Snow));
+        assert!(!weathers.contains(&Weather::Rain));
+    }
+
+    #[test]
+    fn test_river_swell() {
+        let mut climate = Climate::new(400, 0);
+        assert_eq!(climate.river_swell(), 1.0);
+        climate.weather = Weather::Rain;
+        assert_eq!(climate.river_swell(), 1.5);
+    }
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        World::new(
+            M::from_vec(3, 3, vec![
+                0.0, 0.0, 0.0,
+                0.0, 10.0, 10.0,
+                0.0, 10.0, 10.0,
+            ]),
+            vec![],
+            vec![],
+            vec![],
+            0.5
+        )
+    }
+
+    #[test]
+    fn test_snow_settles_above_snowline_and_melts() {
+        let mut climate = Climate::new(400, 0);
+        let mut world = world();
+        climate.weather = Weather::Snow;
+        assert_eq!(climate.update_snow(&mut world), vec![v2(1, 1)]);
+        assert!(almost_equal(world.get_snow(&v2(1, 1)), 0.1));
+        assert_eq!(world.get_snow(&v2(0, 0)), 0.0);
+        climate.snowline = 2.0;
+        assert!(climate.update_snow(&mut world).is_empty());
+        assert_eq!(climate.update_snow(&mut world), vec![v2(1, 1)]);
+        assert_eq!(world.get_snow(&v2(1, 1)), 0.0);
+    }
+}
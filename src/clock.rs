@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+pub struct Clock<T> {
+    step: u64,
+    speed: f64,
+    min_speed: f64,
+    max_speed: f64,
+    max_steps: u32,
+    paused: bool,
+    accumulated: u64,
+    ticks: u64,
+    tasks: Vec<(u64, T)>,
+}
+
+impl<T: Copy> Clock<T> {
+    pub fn new(step: Duration, max_steps: u32) -> Clock<T> {
+        Clock {
+            step: Clock::<T>::to_nanos(step),
+            speed: 1.0,
+            min_speed: 0.25,
+            max_speed: 16.0,
+            max_steps,
+            paused: false,
+            accumulated: 0,
+            ticks: 0,
+            tasks: vec![],
+        }
+    }
+
+    pub fn schedule(&mut self, period: u64, task: T) {
+        assert!(period > 0, "Task period must be positive");
+        self.tasks.push((period, task));
+    }
+
+    pub fn due(&self) -> Vec<T> {
+        self.tasks
+            .iter()
+            .filter(|(period, _)| self.ticks % *period == 0)
+            .map(|(_, task)| *task)
+            .collect()
+    }
+
+    fn to_nanos(duration: Duration) -> u64 {
+        duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos())
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn speed_up(&mut self) {
+        self.speed = (self.speed * 2.0).min(self.max_speed);
+    }
+
+    pub fn slow_down(&mut self) {
+        self.speed = (self.speed / 2.0).max(self.min_speed);
+    }
+
+    pub fn step(&mut self) -> u64 {
+        self.ticks += 1;
+        self.ticks
+    }
+
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        if self.paused {
+            return 0;
+        }
+        self.accumulated += (Clock::<T>::to_nanos(elapsed) as f64 * self.speed) as u64;
+        let mut steps = 0;
+        while self.accumulated >= self.step && steps < self.max_steps {
+            self.accumulated -= self.step;
+            steps += 1;
+        }
+        if steps == self.max_steps {
+            self.accumulated = 0;
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn clock() -> Clock<&'static str> {
+        Clock::new(Duration::from_millis(10), 100)
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut clock = clock();
+        assert_eq!(clock.advance(Duration::from_millis(25)), 2);
+        assert_eq!(clock.advance(Duration::from_millis(5)), 1);
+        assert_eq!(clock.ticks(), 0);
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut clock = clock();
+        clock.toggle_pause();
+        assert!(clock.paused());
+        assert_eq!(clock.advance(Duration::from_millis(50)), 0);
+        clock.toggle_pause();
+        assert_eq!(clock.advance(Duration::from_millis(10)), 1);
+    }
+
+    #[test]
+    fn test_speed() {
+        let mut clock = clock();
+        clock.speed_up();
+        assert_eq!(clock.speed(), 2.0);
+        assert_eq!(clock.advance(Duration::from_millis(20)), 4);
+        clock.slow_down();
+        clock.slow_down();
+        assert_eq!(clock.speed(), 0.5);
+        assert_eq!(clock.advance(Duration::from_millis(20)), 1);
+    }
+
+    #[test]
+    fn test_speed_limits() {
+        let mut clock = clock();
+        for _ in 0..10 {
+            clock.speed_up();
+        }
+        assert_eq!(clock.speed(), 16.0);
+        for _ in 0..10 {
+            clock.slow_down();
+        }
+        assert_eq!(clock.speed(), 0.25);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut clock = clock();
+        assert_eq!(clock.advance(Duration::from_secs(10)), 100);
+        assert_eq!(clock.advance(Duration::from_millis(0)), 0);
+    }
+
+    #[test]
+    fn test_scheduled_tasks() {
+        let mut clock = clock();
+        clock.schedule(2, "often");
+        clock.schedule(3, "rarely");
+        clock.step();
+        assert!(clock.due().is_empty());
+        clock.step();
+        assert_eq!(clock.due(), vec!["often"]);
+        clock.step();
+        assert_eq!(clock.due(), vec!["rarely"]);
+        for _ in 0..3 {
+            clock.step();
+        }
+        assert_eq!(clock.due(), vec!["often", "rarely"]);
+    }
+
+    #[test]
+    fn test_manual_step() {
+        let mut clock = clock();
+        clock.toggle_pause();
+        assert_eq!(clock.step(), 1);
+        assert_eq!(clock.step(), 2);
+        assert_eq!(clock.ticks(), 2);
+    }
+}
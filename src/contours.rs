@@ -0,0 +1,185 @@
+use isometric::terrain::*;
+use isometric::*;
+use std::collections::HashSet;
+
+pub type Segment = (V2<f32>, V2<f32>);
+
+fn get_crossing(elevations: &M<f32>, level: f32, a: V2<usize>, b: V2<usize>) -> Option<V2<f32>> {
+    let ea = elevations[(a.x, a.y)];
+    let eb = elevations[(b.x, b.y)];
+    if (ea >= level) == (eb >= level) {
+        None
+    } else {
+        let t = (level - ea) / (eb - ea);
+        let a = v2(a.x as f32, a.y as f32);
+        let b = v2(b.x as f32, b.y as f32);
+        Some(a + (b - a) * t)
+    }
+}
+
+fn get_cell_segments(elevations: &M<f32>, level: f32, position: &V2<usize>) -> Vec<Segment> {
+    let corners = [
+        *position,
+        v2(position.x + 1, position.y),
+        v2(position.x + 1, position.y + 1),
+        v2(position.x, position.y + 1),
+    ];
+    let crossings: Vec<Option<V2<f32>>> = (0..4)
+        .map(|i| get_crossing(elevations, level, corners[i], corners[(i + 1) % 4]))
+        .collect();
+    let points: Vec<V2<f32>> = crossings.iter().flatten().cloned().collect();
+    if points.len() == 2 {
+        vec![(points[0], points[1])]
+    } else if points.len() == 4 {
+        let center = corners
+            .iter()
+            .map(|corner| elevations[(corner.x, corner.y)])
+            .sum::<f32>()
+            / 4.0;
+        let first_above = elevations[(position.x, position.y)] >= level;
+        if first_above == (center >= level) {
+            vec![(points[0], points[1]), (points[2], points[3])]
+        } else {
+            vec![(points[3], points[0]), (points[1], points[2])]
+        }
+    } else {
+        vec![]
+    }
+}
+
+pub fn get_segments(
+    elevations: &M<f32>,
+    level: f32,
+    from: &V2<usize>,
+    to: &V2<usize>,
+) -> Vec<Segment> {
+    let (width, height) = elevations.shape();
+    let mut out = vec![];
+    for x in from.x..to.x.min(width - 1) {
+        for y in from.y..to.y.min(height - 1) {
+            out.append(&mut get_cell_segments(elevations, level, &v2(x, y)));
+        }
+    }
+    out
+}
+
+fn snap(point: &V2<f32>) -> V2<usize> {
+    v2(point.x.round() as usize, point.y.round() as usize)
+}
+
+fn get_edge(a: V2<usize>, b: V2<usize>) -> Edge {
+    if (a.x, a.y) < (b.x, b.y) {
+        Edge::new(a, b)
+    } else {
+        Edge::new(b, a)
+    }
+}
+
+pub fn snap_segment(elevations: &M<f32>, segment: &Segment) -> Vec<Edge> {
+    let a = snap(&segment.0);
+    let b = snap(&segment.1);
+    if a == b {
+        vec![]
+    } else if a.x == b.x || a.y == b.y {
+        vec![get_edge(a, b)]
+    } else {
+        let c1 = v2(a.x, b.y);
+        let c2 = v2(b.x, a.y);
+        let corner = if elevations[(c1.x, c1.y)] >= elevations[(c2.x, c2.y)] {
+            c1
+        } else {
+            c2
+        };
+        vec![get_edge(a, corner), get_edge(corner, b)]
+    }
+}
+
+pub fn get_contour_edges(
+    elevations: &M<f32>,
+    interval: f32,
+    max_height: f32,
+    from: &V2<usize>,
+    to: &V2<usize>,
+) -> Vec<Edge> {
+    let mut out = HashSet::new();
+    let mut level = interval;
+    while level <= max_height {
+        for segment in get_segments(elevations, level, from, to) {
+            out.extend(snap_segment(elevations, &segment));
+        }
+        level += interval;
+    }
+    out.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[rustfmt::skip]
+    fn slope() -> M<f32> {
+        M::from_vec(3, 3, vec![
+            0.0, 1.0, 2.0,
+            0.0, 1.0, 2.0,
+            0.0, 1.0, 2.0,
+        ])
+    }
+
+    #[rustfmt::skip]
+    fn saddle() -> M<f32> {
+        M::from_vec(2, 2, vec![
+            1.0, 0.0,
+            0.0, 1.0,
+        ])
+    }
+
+    #[test]
+    fn test_get_segments() {
+        let actual = get_segments(&slope(), 0.5, &v2(0, 0), &v2(3, 3));
+        assert_eq!(
+            actual,
+            vec![(v2(0.5, 0.0), v2(0.5, 1.0)), (v2(0.5, 1.0), v2(0.5, 2.0))]
+        );
+    }
+
+    #[test]
+    fn test_get_segments_partial() {
+        let actual = get_segments(&slope(), 1.5, &v2(1, 0), &v2(2, 1));
+        assert_eq!(actual, vec![(v2(1.5, 0.0), v2(1.5, 1.0))]);
+    }
+
+    #[test]
+    fn test_get_segments_saddle() {
+        let actual = get_segments(&saddle(), 0.5, &v2(0, 0), &v2(2, 2));
+        assert_eq!(
+            actual,
+            vec![(v2(0.5, 0.0), v2(1.0, 0.5)), (v2(0.5, 1.0), v2(0.0, 0.5))]
+        );
+    }
+
+    #[test]
+    fn test_snap_straight_segment() {
+        let actual = snap_segment(&slope(), &(v2(0.6, 0.0), v2(0.6, 1.0)));
+        assert_eq!(actual, vec![Edge::new(v2(1, 0), v2(1, 1))]);
+    }
+
+    #[test]
+    fn test_snap_diagonal_segment() {
+        let actual = snap_segment(&slope(), &(v2(0.6, 0.0), v2(0.0, 0.6)));
+        assert_eq!(
+            actual,
+            vec![Edge::new(v2(1, 0), v2(1, 1)), Edge::new(v2(0, 1), v2(1, 1))]
+        );
+    }
+
+    #[test]
+    fn test_get_contour_edges() {
+        let actual = get_contour_edges(&slope(), 1.0, 2.0, &v2(0, 0), &v2(3, 3));
+        assert_eq!(actual.len(), 4);
+        assert!(actual.contains(&Edge::new(v2(1, 0), v2(1, 1))));
+        assert!(actual.contains(&Edge::new(v2(1, 1), v2(1, 2))));
+        assert!(actual.contains(&Edge::new(v2(2, 0), v2(2, 1))));
+        assert!(actual.contains(&Edge::new(v2(2, 1), v2(2, 2))));
+    }
+}
@@ -0,0 +1,434 @@
+use crate::population::Population;
+use crate::resources::Resource;
+use crate::territory::Owner;
+use crate::travel::{get_route_tree, RouteTree, TravelCosts};
+use crate::world::World;
+use isometric::*;
+use std::collections::HashMap;
+
+const GOODS: [Resource; 5] = [
+    Resource::Timber,
+    Resource::Stone,
+    Resource::Soil,
+    Resource::Fish,
+    Resource::Ore,
+];
+
+pub struct Settlement {
+    owner: Owner,
+    houses: Vec<(V2<usize>, f32)>,
+    stock: HashMap<Resource, f32>,
+}
+
+impl Settlement {
+    fn new(owner: Owner, houses: Vec<(V2<usize>, f32)>) -> Settlement {
+        Settlement {
+            owner,
+            houses,
+            stock: HashMap::new(),
+        }
+    }
+
+    fn population(&self) -> f32 {
+        self.houses.iter().map(|(_, population)| population).sum()
+    }
+
+    fn get_stock(&self, resource: Resource) -> f32 {
+        self.stock.get(&resource).cloned().unwrap_or(0.0)
+    }
+
+    fn add_stock(&mut self, resource: Resource, amount: f32) {
+        *self.stock.entry(resource).or_insert(0.0) += amount;
+    }
+
+    fn get_corners(&self, world: &World) -> Vec<V2<usize>> {
+        self.houses
+            .iter()
+            .flat_map(|(house, _)| world.get_corners(house).to_vec())
+            .collect()
+    }
+}
+
+struct Cart {
+    resource: Resource,
+    amount: f32,
+    destination: Owner,
+    remaining: f32,
+}
+
+struct Routes {
+    origin: Vec<V2<usize>>,
+    costs: TravelCosts,
+    version: u64,
+    tree: RouteTree,
+}
+
+pub struct Economy {
+    settlements: Vec<Settlement>,
+    routes: HashMap<Owner, Routes>,
+    carts: Vec<Cart>,
+    harvest_radius: usize,
+    production: f32,
+    consumption: f32,
+    base_price: f32,
+    cart_capacity: f32,
+    transport_cost: f32,
+    max_route: f32,
+    trade_value: f32,
+}
+
+impl Economy {
+    pub fn new(transport_cost: f32) -> Economy {
+        Economy {
+            settlements: vec![],
+            routes: HashMap::new(),
+            carts: vec![],
+            harvest_radius: 2,
+            production: 1.0,
+            consumption: 0.1,
+            base_price: 1.0,
+            cart_capacity: 10.0,
+            transport_cost,
+            max_route: 16000.0,
+            trade_value: 0.0,
+        }
+    }
+
+    pub fn settlements(&self) -> &Vec<Settlement> {
+        &self.settlements
+    }
+
+    pub fn trade_value(&self) -> f32 {
+        self.trade_value
+    }
+
+    fn get_price(&self, settlement: &Settlement, resource: Resource) -> f32 {
+        let demand = settlement.population() * self.consumption * 10.0;
+        self.base_price * (demand + 1.0) / (settlement.get_stock(resource) + 1.0)
+    }
+
+    fn regroup(&mut self, world: &World, population: &Population) {
+        let mut settlements: Vec<Settlement> = vec![];
+        for (house, people) in population.inhabited() {
+            let owner = match world.territory().get(&house) {
+                Some(owner) => owner,
+                None => continue,
+            };
+            match settlements
+                .iter_mut()
+                .find(|settlement| settlement.owner == owner)
+            {
+                Some(settlement) => settlement.houses.push((house, people)),
+                None => settlements.push(Settlement::new(owner, vec![(house, people)])),
+            }
+        }
+        for settlement in settlements.iter_mut() {
+            if let Some(previous) = self
+                .settlements
+                .iter()
+                .find(|previous| previous.owner == settlement.owner)
+            {
+                settlement.stock = previous.stock.clone();
+            }
+        }
+        self.settlements = settlements;
+    }
+
+    fn move_carts(&mut self, elapsed: f32) {
+        for cart in self.carts.iter_mut() {
+            cart.remaining -= elapsed;
+        }
+        let (arrived, travelling): (Vec<Cart>, Vec<Cart>) =
+            self.carts.drain(..).partition(|cart| cart.remaining <= 0.0);
+        self.carts = travelling;
+        for cart in arrived {
+            if let Some(settlement) = self
+                .settlements
+                .iter_mut()
+                .find(|settlement| settlement.owner == cart.destination)
+            {
+                settlement.add_stock(cart.resource, cart.amount);
+            }
+        }
+    }
+
+    fn get_nearby_deposits(&self, world: &World, house: &V2<usize>) -> Vec<V2<usize>> {
+        let mut out = vec![];
+        let radius = self.harvest_radius;
+        for x in house.x.saturating_sub(radius)..=house.x + radius {
+            for y in house.y.saturating_sub(radius)..=house.y + radius {
+                if world.get_deposit(&v2(x, y)).is_some() {
+                    out.push(v2(x, y));
+                }
+            }
+        }
+        out
+    }
+
+    fn produce_and_consume(&mut self, world: &mut World) {
+        for index in 0..self.settlements.len() {
+            let mut produced = vec![];
+            for (house, population) in self.settlements[index].houses.iter() {
+                let mut harvested: Vec<Resource> = vec![];
+                for position in self.get_nearby_deposits(world, house) {
+                    let resource = match world.get_deposit(&position) {
+                        Some(deposit) if !harvested.contains(&deposit.resource) => deposit.resource,
+                        _ => continue,
+                    };
+                    harvested.push(resource);
+                    let amount = world.harvest(&position, self.production * population);
+                    produced.push((resource, amount));
+                }
+            }
+            let settlement = &mut self.settlements[index];
+            for (resource, amount) in produced {
+                settlement.add_stock(resource, amount);
+            }
+            let consumed = self.consumption * settlement.population();
+            for resource in GOODS.iter() {
+                let stock = settlement.get_stock(*resource);
+                settlement
+                    .stock
+                    .insert(*resource, (stock - consumed).max(0.0));
+            }
+        }
+    }
+
+    fn trade(&mut self, world: &mut World, costs: &TravelCosts) {
+        let mut routes = std::mem::take(&mut self.routes);
+        for from in 0..self.settlements.len() {
+            let owner = self.settlements[from].owner;
+            let corners = self.settlements[from].get_corners(world);
+            let stale = match routes.get(&owner) {
+                Some(cached) => {
+                    cached.origin != corners
+                        || cached.costs != *costs
+                        || cached.version != world.version()
+                }
+                None => true,
+            };
+            if stale {
+                let tree = get_route_tree(world, costs, &corners, self.max_route, true);
+                routes.insert(
+                    owner,
+                    Routes {
+                        origin: corners,
+                        costs: *costs,
+                        version: world.version(),
+                        tree,
+                    },
+                );
+            }
+            let tree = &routes[&owner].tree;
+            for to in 0..self.settlements.len() {
+                if from != to {
+                    self.trade_between(world, tree, from, to);
+                }
+            }
+        }
+        let settlements = &self.settlements;
+        routes.retain(|owner, _| {
+            settlements
+                .iter()
+                .any(|settlement| settlement.owner == *owner)
+        });
+        self.routes = routes;
+    }
+
+    fn trade_between(&mut self, world: &mut World, tree: &RouteTree, from: usize, to: usize) {
+        let (route_time, route) = match tree.get_route(&self.settlements[to].get_corners(world)) {
+            Some(route) => route,
+            None => return,
+        };
+        for resource in GOODS.iter() {
+            let margin = self.get_price(&self.settlements[to], *resource)
+                - self.get_price(&self.settlements[from], *resource)
+                - self.transport_cost * route_time;
+            let amount =
+                (self.settlements[from].get_stock(*resource) / 2.0).min(self.cart_capacity);
+            if margin > 0.0 && amount > 0.0 {
+                self.settlements[from].add_stock(*resource, -amount);
+                self.trade_value += margin * amount;
+                for (a, b) in route.iter().zip(route.iter().skip(1)) {
+                    world.record_traffic(a, b, 1.0, self.settlements[from].owner);
+                }
+                self.carts.push(Cart {
+                    resource: *resource,
+                    amount,
+                    destination: self.settlements[to].owner,
+                    remaining: route_time,
+                });
+            }
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        population: &Population,
+        costs: &TravelCosts,
+        elapsed: f32,
+    ) {
+        self.regroup(world, population);
+        self.move_carts(elapsed);
+        self.produce_and_consume(world);
+        self.trade(world, costs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::resources::Deposit;
+    use isometric::terrain::Edge;
+
+    fn world(road: bool) -> World {
+        let mut world = World::new(M::from_element(8, 3, 1.0), vec![], vec![], vec![], 0.5);
+        let mut resources = M::from_element(7, 2, None);
+        resources[(0, 1)] = Some(Deposit::new(Resource::Timber));
+        world.set_resources(resources);
+        if road {
+            for x in 0..7 {
+                world.add_road(&Edge::new(v2(x, 0), v2(x + 1, 0)));
+            }
+        }
+        for house in [v2(0, 0), v2(6, 0)].iter() {
+            let owner = world.territory_mut().new_settlement();
+            world.territory_mut().claim(house, 0, owner);
+        }
+        world
+    }
+
+    fn population() -> Population {
+        let mut population = Population::new(8, 3, 0.5);
+        population.settle(&v2(0, 0), 5.0);
+        population.settle(&v2(6, 0), 5.0);
+        population
+    }
+
+    #[test]
+    fn test_settlements_follow_territory() {
+        let mut world = world(false);
+        let mut population = population();
+        world
+            .territory_mut()
+            .claim(&v2(1, 0), 0, Owner::Settlement(0));
+        population.settle(&v2(1, 0), 5.0);
+        population.settle(&v2(3, 1), 5.0);
+        let mut economy = Economy::new(0.01);
+        economy.regroup(&world, &population);
+        assert_eq!(economy.settlements().len(), 2);
+        assert_eq!(economy.settlements()[0].owner, Owner::Settlement(0));
+        assert_eq!(economy.settlements()[0].houses.len(), 2);
+    }
+
+    #[test]
+    fn test_price_falls_with_stock() {
+        let economy = Economy::new(0.01);
+        let mut settlement = Settlement::new(Owner::Player, vec![(v2(0, 0), 5.0)]);
+        let scarce = economy.get_price(&settlement, Resource::Fish);
+        settlement.add_stock(Resource::Fish, 10.0);
+        assert!(economy.get_price(&settlement, Resource::Fish) < scarce);
+    }
+
+    #[test]
+    fn test_trade_along_road() {
+        let mut world = world(true);
+        let population = population();
+        let costs = TravelCosts::new(10.0, 1.0);
+        let mut economy = Economy::new(0.01);
+        economy.update(&mut world, &population, &costs, 0.0);
+        assert_eq!(economy.settlements().len(), 2);
+        assert_eq!(economy.carts.len(), 1);
+        assert_eq!(economy.carts[0].remaining, 25.0);
+        assert!(economy.trade_value() > 0.0);
+        assert_eq!(
+            world.traffic().get_count(&Edge::new(v2(2, 0), v2(3, 0))),
+            1.0
+        );
+        assert_eq!(
+            world.traffic().get_owner(&Edge::new(v2(2, 0), v2(3, 0))),
+            Some(Owner::Settlement(0))
+        );
+        economy.update(&mut world, &population, &costs, 30.0);
+        let destination = economy
+            .settlements()
+            .iter()
+            .find(|settlement| settlement.owner == Owner::Settlement(1))
+            .unwrap();
+        assert!(destination.get_stock(Resource::Timber) > 0.0);
+    }
+
+    #[test]
+    fn test_no_trade_without_road() {
+        let mut world = world(false);
+        let population = population();
+        let costs = TravelCosts::new(10.0, 1.0);
+        let mut economy = Economy::new(0.01);
+        economy.update(&mut world, &population, &costs, 0.0);
+        assert!(economy.carts.is_empty());
+        assert_eq!(economy.trade_value(), 0.0);
+    }
+
+    #[test]
+    fn test_production_depletes_deposits() {
+        let mut world = world(false);
+        let population = population();
+        let costs = TravelCosts::new(10.0, 1.0);
+        let mut economy = Economy::new(0.01);
+        economy.update(&mut world, &population, &costs, 0.0);
+        assert_eq!(world.get_deposit(&v2(0, 1)).unwrap().amount, 95.0);
+        for _ in 0..19 {
+            economy.update(&mut world, &population, &costs, 0.0);
+        }
+        assert_eq!(world.get_deposit(&v2(0, 1)), None);
+    }
+
+    #[test]
+    fn test_routes_are_kept_until_world_changes() {
+        let mut world = world(true);
+        let population = population();
+        let costs = TravelCosts::new(10.0, 1.0);
+        let mut economy = Economy::new(0.01);
+        economy.update(&mut world, &population, &costs, 0.0);
+        let version = world.version();
+        assert_eq!(economy.routes.len(), 2);
+        assert_eq!(economy.routes[&Owner::Settlement(0)].version, version);
+        world.clear_road(&Edge::new(v2(3, 0), v2(4, 0)));
+        economy.update(&mut world, &population, &costs, 0.0);
+        assert_eq!(
+            economy.routes[&Owner::Settlement(0)].version,
+            world.version()
+        );
+        assert!(economy.routes[&Owner::Settlement(0)]
+            .tree
+            .get_route(&[v2(7, 0)])
+            .is_none());
+    }
+
+    #[test]
+    fn test_settlement_keeps_stock_when_houses_change() {
+        let mut world = world(false);
+        let mut population = population();
+        let mut economy = Economy::new(0.01);
+        economy.regroup(&world, &population);
+        let owner = economy.settlements()[1].owner;
+        economy.carts.push(Cart {
+            resource: Resource::Timber,
+            amount: 1.0,
+            destination: owner,
+            remaining: 0.0,
+        });
+        world.territory_mut().claim(&v2(5, 1), 0, owner);
+        population.settle(&v2(5, 1), 5.0);
+        economy.regroup(&world, &population);
+        population.remove(&v2(6, 0));
+        economy.regroup(&world, &population);
+        economy.move_carts(0.0);
+        let destination = &economy.settlements()[1];
+        assert_eq!(destination.houses, vec![(v2(5, 1), 5.0)]);
+        assert_eq!(destination.owner, owner);
+        assert_eq!(destination.get_stock(Resource::Timber), 1.0);
+    }
+}
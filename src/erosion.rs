@@ -0,0 +1,187 @@
+use crate::world::World;
+use isometric::*;
+use std::collections::HashMap;
+
+pub struct RiverErosion {
+    enabled: bool,
+    rate: f32,
+    valley_rate: f32,
+    widening: f32,
+    max_width: f32,
+    min_change: f32,
+    eroded: HashMap<V2<usize>, f32>,
+}
+
+impl RiverErosion {
+    pub fn new(rate: f32, valley_rate: f32, widening: f32, max_width: f32) -> RiverErosion {
+        RiverErosion {
+            enabled: false,
+            rate,
+            valley_rate,
+            widening,
+            max_width,
+            min_change: 0.01,
+            eroded: HashMap::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) -> bool {
+        self.enabled = !self.enabled;
+        self.enabled
+    }
+
+    fn get_neighbours(world: &World, position: &V2<usize>) -> Vec<V2<usize>> {
+        let mut out = vec![];
+        for x in position.x.saturating_sub(1)..=position.x + 1 {
+            for y in position.y.saturating_sub(1)..=position.y + 1 {
+                let neighbour = v2(x, y);
+                if neighbour != *position && world.in_bounds(&neighbour) {
+                    out.push(neighbour);
+                }
+            }
+        }
+        out
+    }
+
+    fn get_river_widths(world: &World) -> Vec<(V2<usize>, f32)> {
+        let mut widths: HashMap<V2<usize>, f32> = HashMap::new();
+        for node in world.river_nodes() {
+            let width = widths.entry(node.position()).or_insert(0.0);
+            *width = width.max(node.width().max(node.height()));
+        }
+        let mut out: Vec<(V2<usize>, f32)> = widths.into_iter().collect();
+        out.sort_by_key(|(position, _)| (position.x, position.y));
+        out
+    }
+
+    fn get_elevation(&self, world: &World, position: &V2<usize>) -> f32 {
+        world.terrain().elevations()[(position.x, position.y)]
+            - self.eroded.get(position).cloned().unwrap_or(0.0)
+    }
+
+    fn erode(&mut self, world: &World, position: &V2<usize>, elevation: f32) {
+        let depth = self.get_elevation(world, position) - elevation;
+        *self.eroded.entry(*position).or_insert(0.0) += depth;
+    }
+
+    pub fn update(&mut self, world: &mut World, rainfall: f32) -> Vec<V2<usize>> {
+        if !self.enabled || rainfall <= 0.0 {
+            return vec![];
+        }
+        for (position, width) in RiverErosion::get_river_widths(world) {
+            let river = self.get_elevation(world, &position);
+            if river <= world.sea_level() {
+                continue;
+            }
+            let eroded = (river - self.rate * rainfall * width).max(world.sea_level());
+            self.erode(world, &position, eroded);
+            for neighbour in RiverErosion::get_neighbours(world, &position) {
+                let bank = self.get_elevation(world, &neighbour);
+                if bank > river {
+                    let valley = bank - self.valley_rate * rainfall * (bank - river);
+                    self.erode(world, &neighbour, valley);
+                }
+            }
+        }
+        let mut out = vec![];
+        if self.eroded.values().any(|depth| *depth >= self.min_change) {
+            let mut elevations = world.terrain().elevations().clone();
+            for (position, depth) in self.eroded.drain() {
+                elevations[(position.x, position.y)] -= depth;
+                out.push(position);
+            }
+            out.append(&mut world.set_elevations(elevations));
+        }
+        out.append(&mut world.widen_rivers(1.0 + self.widening * rainfall, self.max_width));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use isometric::terrain::*;
+
+    fn almost_equal(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        World::new(
+            M::from_vec(3, 3, vec![
+                3.0, 2.0, 3.0,
+                3.0, 2.0, 3.0,
+                3.0, 2.0, 3.0,
+            ]),
+            vec![
+                Node::new(v2(1, 0), 0.2, 0.0),
+                Node::new(v2(1, 1), 0.2, 0.0),
+            ],
+            vec![
+                Edge::new(v2(1, 0), v2(1, 1)),
+            ],
+            vec![],
+            0.5
+        )
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let mut erosion = RiverErosion::new(1.0, 0.1, 0.1, 0.45);
+        let mut world = world();
+        assert!(erosion.update(&mut world, 1.0).is_empty());
+        assert_eq!(world.get_elevation(&v2(1, 1)), Some(2.0));
+    }
+
+    #[test]
+    fn test_deepens_valley_and_widens_river() {
+        let mut erosion = RiverErosion::new(1.0, 0.1, 0.5, 0.45);
+        let mut world = world();
+        assert!(erosion.toggle());
+        let changed = erosion.update(&mut world, 1.0);
+        assert!(changed.contains(&v2(1, 1)));
+        assert!(changed.contains(&v2(0, 1)));
+        assert!(almost_equal(world.get_elevation(&v2(1, 1)).unwrap(), 1.8));
+        assert!(almost_equal(world.get_elevation(&v2(0, 2)).unwrap(), 2.9));
+        assert!(almost_equal(world.get_elevation(&v2(1, 2)).unwrap(), 2.0));
+        let width = world.get_river_width(&Edge::new(v2(1, 0), v2(1, 1)));
+        assert!(almost_equal(width.unwrap(), 0.3));
+    }
+
+    #[test]
+    fn test_small_changes_wait_for_min_change() {
+        let mut erosion = RiverErosion::new(0.03, 0.0, 0.0, 0.45);
+        let mut world = world();
+        erosion.toggle();
+        erosion.update(&mut world, 1.0);
+        assert_eq!(world.get_elevation(&v2(1, 1)), Some(2.0));
+        erosion.update(&mut world, 1.0);
+        assert!(almost_equal(world.get_elevation(&v2(1, 1)).unwrap(), 1.988));
+    }
+
+    #[test]
+    fn test_junction_is_eroded_once() {
+        let mut erosion = RiverErosion::new(1.0, 0.0, 0.0, 0.45);
+        let mut world = World::new(
+            M::from_element(3, 3, 2.0),
+            vec![Node::new(v2(1, 1), 0.2, 0.0), Node::new(v2(1, 1), 0.0, 0.1)],
+            vec![],
+            vec![],
+            0.5,
+        );
+        erosion.toggle();
+        erosion.update(&mut world, 1.0);
+        assert!(almost_equal(world.get_elevation(&v2(1, 1)).unwrap(), 1.8));
+    }
+
+    #[test]
+    fn test_does_not_erode_below_sea_level() {
+        let mut erosion = RiverErosion::new(100.0, 0.1, 0.0, 0.45);
+        let mut world = world();
+        erosion.toggle();
+        erosion.update(&mut world, 1.0);
+        assert_eq!(world.get_elevation(&v2(1, 1)), Some(0.5));
+    }
+}
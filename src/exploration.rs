@@ -0,0 +1,143 @@
+use crate::world::World;
+use isometric::coords::WorldCoord;
+use isometric::*;
+use std::fs;
+
+pub struct Explorer {
+    eye_height: f32,
+    base_radius: f32,
+    max_radius: usize,
+}
+
+impl Explorer {
+    pub fn new(eye_height: f32, base_radius: f32, max_radius: usize) -> Explorer {
+        Explorer {
+            eye_height,
+            base_radius,
+            max_radius,
+        }
+    }
+
+    pub fn get_radius(&self, world: &World, observer: &V2<usize>) -> usize {
+        let height = match world.get_elevation(observer) {
+            Some(elevation) => (elevation - world.sea_level()).max(0.0),
+            None => 0.0,
+        };
+        let radius = self.base_radius * (1.0 + height).sqrt();
+        (radius as usize).min(self.max_radius)
+    }
+
+    pub fn reveal(&self, world: &mut World, observer: &V2<usize>) -> Vec<V2<usize>> {
+        let elevation = match world.get_elevation(observer) {
+            Some(elevation) => elevation,
+            None => return vec![],
+        };
+        let radius = self.get_radius(world, observer);
+        let world_coord = WorldCoord::new(observer.x as f32, observer.y as f32, elevation);
+        world
+            .viewshed(&world_coord, self.eye_height, radius)
+            .into_iter()
+            .filter(|position| world.explore(position))
+            .collect()
+    }
+}
+
+pub fn to_string(explored: &M<bool>) -> String {
+    let (width, height) = explored.shape();
+    let mut out = String::with_capacity((width + 1) * height);
+    for y in 0..height {
+        for x in 0..width {
+            out.push(if explored[(x, y)] { '1' } else { '0' });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn from_string(text: &str, width: usize, height: usize) -> Option<M<bool>> {
+    let rows: Vec<&[u8]> = text.lines().map(|line| line.as_bytes()).collect();
+    if rows.len() != height || rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+    Some(M::from_fn(width, height, |x, y| rows[y][x] == b'1'))
+}
+
+pub fn save(explored: &M<bool>, path: &str) {
+    if let Err(error) = fs::write(path, to_string(explored)) {
+        println!("Could not save exploration to {}: {}", path, error);
+    }
+}
+
+pub fn load(path: &str, width: usize, height: usize) -> Option<M<bool>> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| from_string(&text, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        World::new(
+            M::from_vec(5, 5, vec![
+                1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 9.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0,
+            ]),
+            vec![],
+            vec![],
+            vec![],
+            0.0
+        )
+    }
+
+    #[test]
+    fn test_higher_ground_sees_further() {
+        let explorer = Explorer::new(0.0, 2.0, 100);
+        let world = world();
+        assert_eq!(explorer.get_radius(&world, &v2(0, 0)), 2);
+        assert_eq!(explorer.get_radius(&world, &v2(3, 2)), 6);
+    }
+
+    #[test]
+    fn test_max_radius() {
+        let explorer = Explorer::new(0.0, 2.0, 4);
+        assert_eq!(explorer.get_radius(&world(), &v2(3, 2)), 4);
+    }
+
+    #[test]
+    fn test_reveal() {
+        let explorer = Explorer::new(0.0, 3.0, 100);
+        let mut world = world();
+        let revealed = explorer.reveal(&mut world, &v2(2, 2));
+        assert!(revealed.contains(&v2(2, 2)));
+        assert!(revealed.contains(&v2(0, 2)));
+        assert!(revealed.contains(&v2(3, 2)));
+        assert!(!revealed.contains(&v2(4, 2)));
+        assert!(!revealed.contains(&v2(4, 1)));
+        assert!(world.is_explored(&v2(0, 2)));
+        assert!(!world.is_explored(&v2(4, 2)));
+        assert!(explorer.reveal(&mut world, &v2(2, 2)).is_empty());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut explored = M::from_element(3, 2, false);
+        explored[(0, 0)] = true;
+        explored[(2, 1)] = true;
+        let text = to_string(&explored);
+        assert_eq!(text, "100\n001\n");
+        assert_eq!(from_string(&text, 3, 2), Some(explored));
+    }
+
+    #[test]
+    fn test_from_string_wrong_size() {
+        assert_eq!(from_string("100\n001\n", 2, 2), None);
+        assert_eq!(from_string("100\n001\n", 3, 3), None);
+    }
+}
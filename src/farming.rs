@@ -0,0 +1,205 @@
+use crate::resources::Resource;
+use crate::world::World;
+use isometric::*;
+
+pub struct Farms {
+    max_gradient: f32,
+    base_yield: f32,
+    river_radius: usize,
+    food: f32,
+}
+
+impl Farms {
+    pub fn new(max_gradient: f32, base_yield: f32) -> Farms {
+        Farms {
+            max_gradient,
+            base_yield,
+            river_radius: 4,
+            food: 0.0,
+        }
+    }
+
+    pub fn food(&self) -> f32 {
+        self.food
+    }
+
+    fn can_farm(&self, world: &World, position: &V2<usize>) -> bool {
+        world.in_bounds(&v2(position.x + 1, position.y + 1))
+            && world.get_lowest_corner(position) >= world.sea_level()
+            && !world.is_lake(position)
+            && world.get_max_abs_rise(position) < self.max_gradient
+    }
+
+    pub fn consume(&mut self, demand: f32) -> f32 {
+        let eaten = demand.min(self.food);
+        self.food -= eaten;
+        eaten
+    }
+
+    pub fn zone(&self, world: &mut World, position: &V2<usize>, occupied: bool) -> bool {
+        if world.is_farmland(position) {
+            world.set_farmland(position, false);
+            true
+        } else if !occupied && !world.has_road(position) && self.can_farm(world, position) {
+            world.set_farmland(position, true);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn get_river_distance(&self, world: &World, position: &V2<usize>) -> Option<usize> {
+        let radius = self.river_radius;
+        let gap = |corner: usize, tile: usize| {
+            if corner < tile {
+                tile - corner
+            } else {
+                corner.saturating_sub(tile + 1)
+            }
+        };
+        let mut out: Option<usize> = None;
+        for x in position.x.saturating_sub(radius)..=position.x + radius + 1 {
+            for y in position.y.saturating_sub(radius)..=position.y + radius + 1 {
+                if !world.in_bounds(&v2(x, y)) {
+                    continue;
+                }
+                let node = world.rivers().get_node(v2(x, y));
+                if node.width() > 0.0 || node.height() > 0.0 {
+                    let distance = gap(x, position.x).max(gap(y, position.y));
+                    out = Some(out.map_or(distance, |best| best.min(distance)));
+                }
+            }
+        }
+        out
+    }
+
+    pub fn get_yield(&self, world: &World, position: &V2<usize>) -> f32 {
+        if !self.can_farm(world, position) {
+            return 0.0;
+        }
+        let soil = match world.get_deposit(position) {
+            Some(deposit) if deposit.resource == Resource::Soil => 1.0,
+            _ => 0.5,
+        };
+        let terrain = 1.0 - world.get_max_abs_rise(position) / self.max_gradient;
+        let river = match self.get_river_distance(world, position) {
+            Some(distance) => 1.0 - 0.5 * distance as f32 / self.river_radius as f32,
+            None => 0.5,
+        };
+        self.base_yield * soil * terrain * river
+    }
+
+    pub fn update(&mut self, world: &World, factor: f32) -> f32 {
+        let mut out = 0.0;
+        for x in 0..world.width() - 1 {
+            for y in 0..world.height() - 1 {
+                let position = v2(x, y);
+                if world.is_farmland(&position) {
+                    out += self.get_yield(world, &position) * factor;
+                }
+            }
+        }
+        self.food += out;
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::resources::Deposit;
+    use isometric::terrain::*;
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        World::new(
+            M::from_vec(8, 4, vec![
+                1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 3.0,
+                0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+            ]),
+            vec![
+                Node::new(v2(0, 0), 0.2, 0.0),
+                Node::new(v2(0, 1), 0.2, 0.0),
+            ],
+            vec![
+                Edge::new(v2(0, 0), v2(0, 1)),
+            ],
+            vec![],
+            0.5
+        )
+    }
+
+    #[test]
+    fn test_zone() {
+        let farms = Farms::new(0.5, 1.0);
+        let mut world = world();
+        assert!(farms.zone(&mut world, &v2(1, 0), false));
+        assert!(world.is_farmland(&v2(1, 0)));
+        assert!(farms.zone(&mut world, &v2(1, 0), false));
+        assert!(!world.is_farmland(&v2(1, 0)));
+    }
+
+    #[test]
+    fn test_cannot_zone_steep_or_underwater() {
+        let farms = Farms::new(0.5, 1.0);
+        let mut world = world();
+        assert!(!farms.zone(&mut world, &v2(6, 1), false));
+        assert!(!farms.zone(&mut world, &v2(1, 2), false));
+        assert!(!world.is_farmland(&v2(6, 1)));
+    }
+
+    #[test]
+    fn test_cannot_zone_occupied_or_road() {
+        let farms = Farms::new(0.5, 1.0);
+        let mut world = world();
+        assert!(!farms.zone(&mut world, &v2(1, 0), true));
+        world.add_road(&Edge::new(v2(3, 0), v2(3, 1)));
+        assert!(!farms.zone(&mut world, &v2(2, 0), false));
+        assert!(!farms.zone(&mut world, &v2(3, 0), false));
+        assert!(farms.zone(&mut world, &v2(4, 0), false));
+    }
+
+    #[test]
+    fn test_consume() {
+        let mut farms = Farms::new(0.5, 1.0);
+        farms.food = 1.0;
+        assert_eq!(farms.consume(0.25), 0.25);
+        assert_eq!(farms.consume(1.0), 0.75);
+        assert_eq!(farms.food(), 0.0);
+    }
+
+    #[test]
+    fn test_river_distance() {
+        let farms = Farms::new(0.5, 1.0);
+        let world = world();
+        assert_eq!(farms.get_river_distance(&world, &v2(0, 0)), Some(0));
+        assert_eq!(farms.get_river_distance(&world, &v2(2, 0)), Some(2));
+        assert_eq!(farms.get_river_distance(&world, &v2(6, 0)), None);
+    }
+
+    #[test]
+    fn test_yield() {
+        let farms = Farms::new(0.5, 1.0);
+        let mut world = world();
+        assert_eq!(farms.get_yield(&world, &v2(0, 0)), 0.5);
+        assert_eq!(farms.get_yield(&world, &v2(6, 0)), 0.25);
+        let mut resources = M::from_element(7, 3, None);
+        resources[(0, 0)] = Some(Deposit::new(Resource::Soil));
+        world.set_resources(resources);
+        assert_eq!(farms.get_yield(&world, &v2(0, 0)), 1.0);
+    }
+
+    #[test]
+    fn test_update() {
+        let mut farms = Farms::new(0.5, 1.0);
+        let mut world = world();
+        farms.zone(&mut world, &v2(0, 0), false);
+        farms.zone(&mut world, &v2(6, 0), false);
+        assert_eq!(farms.update(&world, 1.0), 0.75);
+        assert_eq!(farms.update(&world, 2.0), 1.5);
+        assert_eq!(farms.food(), 2.25);
+    }
+}
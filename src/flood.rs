@@ -0,0 +1,151 @@
+use crate::world::World;
+use isometric::*;
+
+#[derive(Clone, Copy)]
+pub struct Floods {
+    flood_height: f32,
+    radius: usize,
+    house_damage: f32,
+    road_damage: f32,
+}
+
+fn get_river_level(world: &World, position: &V2<usize>, radius: usize) -> Option<f32> {
+    let mut out: Option<f32> = None;
+    for x in position.x.saturating_sub(radius)..=position.x + radius + 1 {
+        for y in position.y.saturating_sub(radius)..=position.y + radius + 1 {
+            let corner = v2(x, y);
+            if !world.in_bounds(&corner) {
+                continue;
+            }
+            let node = world.rivers().get_node(corner);
+            if node.width() > 0.0 || node.height() > 0.0 {
+                let elevation = world.get_elevation(&corner).unwrap();
+                out = Some(out.map_or(elevation, |level| level.max(elevation)));
+            }
+        }
+    }
+    out
+}
+
+fn get_flood_risk(world: &World, position: &V2<usize>, radius: usize, max_rise: f32) -> f32 {
+    match get_river_level(world, position, radius) {
+        Some(level) => {
+            let height = world.get_lowest_corner(position) - level;
+            (1.0 - height / max_rise).clamp(0.0, 1.0)
+        }
+        None => 0.0,
+    }
+}
+
+impl Floods {
+    pub fn new(flood_height: f32, house_damage: f32, road_damage: f32) -> Floods {
+        Floods {
+            flood_height,
+            radius: 1,
+            house_damage,
+            road_damage,
+        }
+    }
+
+    fn is_flooded(&self, world: &World, position: &V2<usize>, rise: f32) -> bool {
+        if rise <= 0.0 || world.get_lowest_corner(position) < world.sea_level() {
+            return false;
+        }
+        match get_river_level(world, position, self.radius) {
+            Some(level) => world.get_lowest_corner(position) < level + rise,
+            None => false,
+        }
+    }
+
+    pub fn get_risk(&self, world: &World, position: &V2<usize>) -> f32 {
+        get_flood_risk(world, position, self.radius, self.flood_height)
+    }
+
+    pub fn house_damage(&self) -> f32 {
+        self.house_damage
+    }
+
+    pub fn road_damage(&self) -> f32 {
+        self.road_damage
+    }
+
+    pub fn update(&self, world: &mut World, swell: f32) -> Vec<V2<usize>> {
+        let rise = (swell - 1.0) * self.flood_height;
+        let mut out = vec![];
+        for x in 0..world.width() - 1 {
+            for y in 0..world.height() - 1 {
+                let position = v2(x, y);
+                let flooded = self.is_flooded(world, &position, rise);
+                if flooded != world.is_flooded(&position) {
+                    world.set_flooded(&position, flooded);
+                    out.push(position);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use isometric::terrain::*;
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        World::new(
+            M::from_vec(6, 3, vec![
+                1.0, 1.0, 1.6, 1.6, 3.0, 3.0,
+                1.0, 1.0, 1.6, 1.6, 3.0, 3.0,
+                1.0, 1.0, 1.6, 1.6, 3.0, 3.0,
+            ]),
+            vec![
+                Node::new(v2(0, 0), 0.0, 0.2),
+                Node::new(v2(1, 0), 0.0, 0.2),
+            ],
+            vec![
+                Edge::new(v2(0, 0), v2(1, 0)),
+            ],
+            vec![],
+            0.5
+        )
+    }
+
+    #[test]
+    fn test_no_flood_without_rain() {
+        let floods = Floods::new(1.0, 0.5, 5.0);
+        let mut world = world();
+        assert!(floods.update(&mut world, 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_low_tiles_near_river_flood() {
+        let floods = Floods::new(1.0, 0.5, 5.0);
+        let mut world = world();
+        let flooded = floods.update(&mut world, 1.5);
+        assert_eq!(flooded, vec![v2(0, 0), v2(0, 1), v2(1, 0), v2(1, 1)]);
+        assert!(world.is_flooded(&v2(1, 1)));
+        assert!(!world.is_flooded(&v2(2, 0)));
+        assert_eq!(floods.update(&mut world, 1.0).len(), 4);
+        assert!(!world.is_flooded(&v2(1, 1)));
+    }
+
+    #[test]
+    fn test_flood_risk() {
+        let world = world();
+        assert_eq!(get_flood_risk(&world, &v2(0, 0), 1, 0.5), 1.0);
+        assert_eq!(get_flood_risk(&world, &v2(2, 0), 1, 0.5), 0.0);
+        assert!((get_flood_risk(&world, &v2(2, 0), 1, 1.0) - 0.4).abs() < 0.0001);
+        assert_eq!(get_flood_risk(&world, &v2(3, 0), 1, 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_risk_uses_flood_height() {
+        let world = world();
+        let floods = Floods::new(1.0, 0.5, 5.0);
+        assert!((floods.get_risk(&world, &v2(2, 0)) - 0.4).abs() < 0.0001);
+        let floods = Floods::new(0.5, 0.5, 5.0);
+        assert_eq!(floods.get_risk(&world, &v2(2, 0)), 0.0);
+    }
+}
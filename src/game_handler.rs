@@ -1,18 +1,47 @@
 use crate::avatar::*;
+use crate::climate::*;
+use crate::clock::*;
+use crate::economy::*;
+use crate::erosion::RiverErosion;
+use crate::exploration;
+use crate::exploration::Explorer;
+use crate::farming::*;
+use crate::flood::Floods;
 use crate::house_builder::*;
 use crate::label_editor::*;
+use crate::minimap::*;
+use crate::palette::*;
+use crate::party::*;
+use crate::population::*;
+use crate::resources::*;
+use crate::sun::*;
+use crate::territory::Owner;
+use crate::tide::*;
+use crate::travel::*;
 use crate::world::*;
 use crate::world_artist::*;
 
 use isometric::coords::*;
-use isometric::terrain::*;
+use isometric::v2;
 use isometric::EventHandler;
-use isometric::{v2, V3};
 use isometric::{Command, Event};
-use isometric::{ElementState, VirtualKeyCode};
+use isometric::{ElementState, MouseButton, VirtualKeyCode};
 
 use std::f32::consts::PI;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const UPDATE_PERIOD: u64 = 600;
+
+#[derive(Clone, Copy)]
+enum Task {
+    Erode,
+    Save,
+    Grow,
+    Travel,
+    Precipitation,
+    Weather,
+}
 
 pub struct GameHandler {
     world: World,
@@ -20,113 +49,684 @@ pub struct GameHandler {
     world_coord: Option<WorldCoord>,
     label_editor: LabelEditor,
     house_builder: HouseBuilder,
-    avatar: Avatar,
+    party: Party,
+    tide: Tide,
+    minimap: Minimap,
+    palette_loader: PaletteLoader,
+    sun: Sun,
+    clock: Clock<Task>,
+    last_frame: Option<Instant>,
+    explorer: Explorer,
+    exploration_path: String,
+    exploration_changed: bool,
+    showing_viewshed: bool,
+    base_costs: TravelCosts,
+    travel_costs: TravelCosts,
+    travel_readout: TravelReadout,
+    population: Population,
+    harvesters: Harvesters,
+    economy: Economy,
+    farms: Farms,
+    climate: Climate,
+    floods: Floods,
+    erosion: RiverErosion,
 }
 
 impl GameHandler {
-    pub fn new(world: World) -> GameHandler {
-        let cliff_gradient = 0.53;
-        let beach_level = world.sea_level() + 0.05;
-        let light_direction = V3::new(-1.0, 0.0, 1.0);
-        let world_artist =
-            WorldArtist::new(&world, 64, cliff_gradient, beach_level, light_direction);
+    pub fn new(
+        mut world: World,
+        exploration_path: String,
+        seed: u8,
+        cliff_gradient: f32,
+    ) -> GameHandler {
+        if let Some(explored) = exploration::load(&exploration_path, world.width(), world.height())
+        {
+            world.set_explored(explored);
+        }
+        let style = TerrainStyle {
+            cliff_gradient,
+            beach_height: 0.05,
+            contour_interval: 1.0,
+        };
+        let floods = Floods::new(1.0, 0.5, 5.0);
+        let travel_costs = TravelCosts::new(16.0, cliff_gradient);
+        let sun = Sun::new(36000, 96, 0.375);
+        let light_direction = sun.light_direction();
+        let mut palette_loader = PaletteLoader::new("palette.csv", Duration::from_secs(1));
+        let palette = palette_loader.load().unwrap_or_else(Palette::standard);
+        let mut clock = Clock::new(Duration::from_millis(16), 64);
+        clock.schedule(6000, Task::Erode);
+        clock.schedule(UPDATE_PERIOD, Task::Save);
+        clock.schedule(UPDATE_PERIOD, Task::Grow);
+        clock.schedule(UPDATE_PERIOD, Task::Travel);
+        clock.schedule(UPDATE_PERIOD, Task::Precipitation);
+        clock.schedule(6000, Task::Weather);
+        let world_artist = WorldArtist::new(&world, 64, style, floods, light_direction, palette);
         GameHandler {
-            house_builder: HouseBuilder::new(world.width(), world.height(), light_direction),
+            palette_loader,
+            sun,
+            clock,
+            last_frame: None,
+            explorer: Explorer::new(0.1, 16.0, 128),
+            exploration_path,
+            exploration_changed: false,
+            showing_viewshed: false,
+            base_costs: travel_costs,
+            travel_costs,
+            travel_readout: TravelReadout::new(1500.0, 16000.0),
+            population: Population::new(world.width(), world.height(), cliff_gradient),
+            harvesters: Harvesters::new(1.0, light_direction, palette.harvester),
+            economy: Economy::new(0.001),
+            farms: Farms::new(cliff_gradient / 2.0, 1.0),
+            climate: Climate::new(36000 * 16, seed),
+            floods,
+            erosion: RiverErosion::new(0.01, 0.001, 0.01, 0.45),
+            tide: Tide::new(world.sea_level(), 0.1, 3600, 0.0, 0.01),
+            minimap: Minimap::new(&world, 256, 64.0),
+            house_builder: HouseBuilder::new(
+                world.width(),
+                world.height(),
+                light_direction,
+                palette.house,
+            ),
             world,
             world_artist,
             world_coord: None,
             label_editor: LabelEditor::new(),
-            avatar: Avatar::new(0.00078125, cliff_gradient),
+            party: Party::new(0.00078125, travel_costs),
         }
     }
 }
 
 impl GameHandler {
-    fn build_road(&mut self) -> Vec<Command> {
-        let from = self.avatar.position();
-        self.avatar.walk(&self.world);
-        let to = self.avatar.position();
+    fn walk(&mut self, index: usize) {
+        let from = self.party.get(index).position();
+        self.party.get_mut(index).walk(&self.world);
+        let to = self.party.get(index).position();
+        if let (Some(from), Some(to)) = (from, to) {
+            let from = v2(from.x as usize, from.y as usize);
+            let to = v2(to.x as usize, to.y as usize);
+            if from != to && !is_sailing(&self.world, &from, &to) {
+                self.world.record_traffic(&from, &to, 1.0, Owner::Player);
+            }
+        }
+    }
+
+    fn build_road(&mut self, index: usize) -> Vec<Command> {
+        let from = self.party.get(index).position();
+        self.walk(index);
+        let to = self.party.get(index).position();
         match (from, to) {
             (Some(from), Some(to)) if from != to => {
                 let from = v2(from.x as usize, from.y as usize);
                 let to = v2(to.x as usize, to.y as usize);
 
-                let edge = Edge::new(from, to);
-                self.world.toggle_road(&edge);
-                let mut commands = self.world_artist.draw_affected(&self.world, vec![from, to]);
-                commands.append(&mut self.avatar.draw());
+                let edges = self.world.get_road_edges(&from, &to);
+                let mut affected = vec![from, to];
+                if !edges
+                    .iter()
+                    .all(|edge| self.world.can_build_road(edge, Owner::Player))
+                {
+                    println!("Cannot build road on water or land owned by others");
+                } else if edges.iter().all(|edge| self.world.roads().is_road(edge)) {
+                    for edge in edges {
+                        self.world.clear_road(&edge);
+                        affected.append(&mut self.world.release_road(&edge));
+                    }
+                } else {
+                    for edge in edges {
+                        self.world.add_road(&edge);
+                        affected.append(&mut self.world.claim_road(&edge, Owner::Player));
+                    }
+                }
+                let mut commands = self.explore(index);
+                commands.append(&mut self.world_artist.draw_affected(&self.world, affected));
+                commands.append(&mut self.party.draw(index, &self.world));
                 commands
             }
             _ => vec![],
         }
     }
 
+    fn explore(&mut self, index: usize) -> Vec<Command> {
+        match self.party.get(index).position() {
+            Some(position) => {
+                let position = v2(position.x as usize, position.y as usize);
+                let explored = self.explorer.reveal(&mut self.world, &position);
+                self.exploration_changed |= !explored.is_empty();
+                self.world_artist.draw_affected(&self.world, explored)
+            }
+            None => vec![],
+        }
+    }
+
+    fn add_avatar(&mut self) -> Vec<Command> {
+        let mut commands = self.party.add(self.world_coord, &self.world);
+        if let Some(index) = self.party.selected_index() {
+            commands.append(&mut self.explore(index));
+        }
+        commands
+    }
+
+    fn reposition_avatar(&mut self) -> Vec<Command> {
+        match self.party.selected_index() {
+            Some(index) => {
+                self.party
+                    .get_mut(index)
+                    .reposition(self.world_coord, &self.world);
+                let mut commands = self.explore(index);
+                commands.append(&mut self.party.draw(index, &self.world));
+                commands
+            }
+            None => self.add_avatar(),
+        }
+    }
+
+    fn give_order(&mut self, order: Order) -> Vec<Command> {
+        if let Some(avatar) = self.party.selected_mut() {
+            avatar.give_order(order);
+        }
+        vec![]
+    }
+
+    fn go_to(&mut self) -> Vec<Command> {
+        match self.world_coord {
+            Some(world_coord) => {
+                let target = v2(
+                    world_coord.x.round() as usize,
+                    world_coord.y.round() as usize,
+                );
+                self.give_order(Order::GoTo(target))
+            }
+            None => vec![],
+        }
+    }
+
+    fn run_order(&mut self, index: usize, order: Order) -> Vec<Command> {
+        match order {
+            Order::Walk => {
+                self.walk(index);
+                self.explore(index)
+            }
+            Order::BuildRoad => self.build_road(index),
+            Order::GoTo(target) => {
+                let from = self.party.get(index).position();
+                self.party.get_mut(index).face(&target);
+                self.walk(index);
+                let avatar = self.party.get_mut(index);
+                match avatar.position() {
+                    Some(to) if Some(to) != from && v2(to.x as usize, to.y as usize) != target => {
+                        avatar.resume_order(order)
+                    }
+                    _ => (),
+                }
+                self.explore(index)
+            }
+        }
+    }
+
+    fn run_orders(&mut self) -> Vec<Command> {
+        let mut commands = vec![];
+        for index in 0..self.party.avatars().len() {
+            if let Some(order) = self.party.get_mut(index).next_order() {
+                commands.append(&mut self.run_order(index, order));
+            }
+        }
+        commands
+    }
+
+    fn save_exploration(&mut self) {
+        if self.exploration_changed {
+            exploration::save(self.world.explored(), &self.exploration_path);
+            self.exploration_changed = false;
+        }
+    }
+
+    fn toggle_viewshed(&mut self) -> Vec<Command> {
+        let viewshed = match self.world_coord {
+            Some(world_coord) if !self.showing_viewshed => {
+                Some(self.world.viewshed(&world_coord, 0.5, 256))
+            }
+            _ => None,
+        };
+        self.showing_viewshed = viewshed.is_some();
+        self.world_artist.show_viewshed(&self.world, viewshed)
+    }
+
+    fn draw_travel_estimate(&mut self) -> Vec<Command> {
+        self.travel_readout.draw(
+            &self.world,
+            &self.travel_costs,
+            self.party.selected().and_then(|avatar| avatar.position()),
+            self.world_coord,
+        )
+    }
+
     fn rotate(&self, yaw: f32) -> Vec<Command> {
         let mut commands = vec![Command::Rotate {
             center: GLCoord4D::new(0.0, 0.0, 0.0, 1.0),
             yaw,
         }];
-        commands.append(&mut self.avatar.draw());
+        commands.append(&mut self.party.draw_all(&self.world));
+        commands
+    }
+
+    fn update_sea_level(&mut self) -> Vec<Command> {
+        let sea_level = self.tide.level();
+        if sea_level != self.world.sea_level() {
+            self.world.set_sea_level(sea_level);
+            self.world_artist.draw_sea_level_change(&self.world)
+        } else {
+            vec![]
+        }
+    }
+
+    fn draw_minimap(&mut self) -> Vec<Command> {
+        let labels = self.label_editor.labels();
+        self.minimap.draw(
+            &self.world,
+            &MinimapFeatures {
+                version: self.world_artist.version()
+                    + self.house_builder.version()
+                    + labels.len() as u64,
+                colors: self.world_artist.colors(),
+                palette: self.world_artist.palette(),
+                houses: self.house_builder.houses(),
+                labels,
+                avatar: self.party.selected().and_then(|avatar| avatar.position()),
+            },
+        )
+    }
+
+    fn toggle_minimap(&mut self) -> Vec<Command> {
+        self.minimap.toggle();
+        self.draw_minimap()
+    }
+
+    fn jump_to(&mut self) -> Vec<Command> {
+        match self
+            .world_coord
+            .and_then(|world_coord| self.minimap.get_target(&self.world, world_coord))
+        {
+            Some(target) => vec![Command::LookAt(target)],
+            None => match self.world_coord {
+                Some(world_coord) => self.party.select_at(world_coord, 1.0),
+                None => vec![],
+            },
+        }
+    }
+
+    fn reload_palette(&mut self) -> Vec<Command> {
+        match self.palette_loader.load() {
+            Some(palette) => {
+                self.house_builder.set_color(palette.house);
+                self.harvesters.set_color(palette.harvester);
+                self.world_artist.set_palette(&self.world, palette)
+            }
+            None => vec![],
+        }
+    }
+
+    fn update_sun(&mut self) {
+        let light_direction = self.sun.light_direction();
+        let tint = tint(&self.sun.tint(), &self.climate.season().tint());
+        self.world_artist.set_light(light_direction, tint);
+        self.house_builder.set_light(light_direction, tint);
+        self.harvesters.set_light(light_direction, tint);
+    }
+
+    fn update_climate(&mut self) -> Vec<Command> {
+        let season = self.climate.season();
+        let weather = self.climate.weather();
+        self.travel_costs = self.base_costs.scale(weather.travel_factor());
+        self.party.set_costs(self.travel_costs);
+        let mut commands = vec![];
+        if season != self.world.season() {
+            self.world.set_season(season);
+            commands.append(&mut self.world_artist.redraw_colors(&self.world));
+            self.update_sun();
+        }
+        let swollen = self.world.set_river_swell(self.climate.river_swell());
+        commands.append(&mut self.world_artist.draw_affected(&self.world, swollen));
+        commands
+    }
+
+    fn erode(&mut self) -> Vec<Command> {
+        let eroded = self
+            .erosion
+            .update(&mut self.world, self.climate.rainfall());
+        let mut commands = self.world_artist.draw_affected(&self.world, eroded);
+        commands.append(&mut self.flood());
+        commands
+    }
+
+    fn toggle_erosion(&mut self) -> Vec<Command> {
+        if self.erosion.toggle() {
+            println!("Erosion enabled");
+        } else {
+            println!("Erosion disabled");
+        }
+        vec![]
+    }
+
+    fn flood(&mut self) -> Vec<Command> {
+        let changed = self
+            .floods
+            .update(&mut self.world, self.climate.river_swell());
+        for position in changed.iter() {
+            if self.world.is_flooded(position) {
+                self.population.damage(position, self.floods.house_damage());
+                self.world.damage_roads(position, self.floods.road_damage());
+            }
+        }
+        self.world_artist.draw_affected(&self.world, changed)
+    }
+
+    fn update_population(&mut self) -> Vec<Command> {
+        let mut commands = vec![];
+        let demand = self.population.food_demand();
+        let changes = self
+            .population
+            .update(&self.world, &self.harvesters, self.farms.food());
+        self.farms.consume(demand);
+        for change in changes {
+            commands.append(&mut match change {
+                HouseChange::Built(position, parent) => {
+                    let territory = self.world.territory_mut();
+                    let mut claimed = vec![];
+                    let owner = match territory.get(&parent) {
+                        Some(owner) => owner,
+                        None => {
+                            let owner = territory.new_settlement();
+                            claimed.append(&mut territory.claim(&parent, 2, owner));
+                            owner
+                        }
+                    };
+                    claimed.append(&mut territory.claim(&position, 2, owner));
+                    let mut commands = self.house_builder.add_house(&self.world, &position);
+                    commands.append(&mut self.world_artist.draw_affected(&self.world, claimed));
+                    commands
+                }
+                HouseChange::Abandoned(position) => {
+                    self.house_builder
+                        .set_abandoned(&self.world, &position, true)
+                }
+                HouseChange::Resettled(position) => {
+                    self.house_builder
+                        .set_abandoned(&self.world, &position, false)
+                }
+                HouseChange::Collapsed(position) => {
+                    let territory = self.world.territory_mut();
+                    let released = match territory.get(&position) {
+                        Some(owner) => territory.release(&position, 2, owner),
+                        None => vec![],
+                    };
+                    let mut commands = self.house_builder.remove_house(&position);
+                    commands.append(&mut self.world_artist.draw_affected(&self.world, released));
+                    commands
+                }
+            });
+        }
+        commands
+    }
+
+    fn run_task(&mut self, task: Task) -> Vec<Command> {
+        match task {
+            Task::Erode => self.erode(),
+            Task::Save => {
+                self.save_exploration();
+                vec![]
+            }
+            Task::Grow => {
+                self.farms.update(&self.world, self.climate.yield_factor());
+                let mut commands = self.update_population();
+                commands.append(&mut self.harvesters.update(&mut self.world));
+                commands
+            }
+            Task::Travel => {
+                let elapsed = UPDATE_PERIOD as f32;
+                self.economy.update(
+                    &mut self.world,
+                    &self.population,
+                    &self.travel_costs,
+                    elapsed,
+                );
+                let worn = self.world.wear_roads();
+                let mut commands = self.world_artist.draw_affected(&self.world, worn);
+                commands.append(&mut self.world_artist.redraw_overlay(&self.world));
+                commands
+            }
+            Task::Precipitation => {
+                let snow = self.climate.update_snow(&mut self.world);
+                let mut commands = self.world_artist.draw_affected(&self.world, snow);
+                commands.append(&mut self.flood());
+                commands
+            }
+            Task::Weather => {
+                if self.climate.update_weather() {
+                    self.update_climate()
+                } else {
+                    vec![]
+                }
+            }
+        }
+    }
+
+    fn step(&mut self) -> Vec<Command> {
+        let mut commands = vec![];
+        self.clock.step();
+        for task in self.clock.due() {
+            commands.append(&mut self.run_task(task));
+        }
+        self.tide.tick();
+        if self.climate.tick() {
+            commands.append(&mut self.update_climate());
+        }
+        commands.append(&mut self.party.tick(&self.world));
+        commands.append(&mut self.run_orders());
+        commands.append(&mut self.update_sea_level());
+        if self.sun.tick() {
+            self.update_sun();
+        }
         commands
     }
 
+    fn advance_clock(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = match self.last_frame {
+            Some(last_frame) => now.duration_since(last_frame),
+            None => Duration::from_millis(0),
+        };
+        self.last_frame = Some(now);
+        self.clock.advance(elapsed)
+    }
+
+    fn print_clock(&self) -> Vec<Command> {
+        println!(
+            "Tick {} at {}x{}, {:?} {:?}, population {:.0}, stock {:?}, food {:.0}, {} settlements, trade {:.0}",
+            self.clock.ticks(),
+            self.clock.speed(),
+            if self.clock.paused() { " (paused)" } else { "" },
+            self.climate.season(),
+            self.climate.weather(),
+            self.population.total(),
+            self.harvesters.stock(),
+            self.farms.food(),
+            self.economy.settlements().len(),
+            self.economy.trade_value()
+        );
+        vec![]
+    }
+
+    fn tick(&mut self) -> Vec<Command> {
+        let mut commands = vec![];
+        for _ in 0..self.advance_clock() {
+            commands.append(&mut self.step());
+        }
+        commands.append(&mut self.reload_palette());
+        commands.append(&mut self.world_artist.draw_pending(&self.world, 4));
+        commands.append(&mut self.house_builder.draw_pending(&self.world, 64));
+        commands.append(&mut self.harvesters.draw_pending(&self.world, 64));
+        commands.append(&mut self.draw_travel_estimate());
+        commands
+    }
+
+    fn build_harvester(&mut self) -> Vec<Command> {
+        match self.world_coord {
+            Some(world_coord) => {
+                let position = v2(world_coord.x as usize, world_coord.y as usize);
+                if !self.world.territory().can_build(&position, Owner::Player) {
+                    println!("Cannot build harvester on land owned by others");
+                    return vec![];
+                }
+                let mut commands = self.harvesters.build(&self.world, position);
+                if !commands.is_empty() {
+                    let claimed = self
+                        .world
+                        .territory_mut()
+                        .claim(&position, 1, Owner::Player);
+                    commands.append(&mut self.world_artist.draw_affected(&self.world, claimed));
+                }
+                commands
+            }
+            None => vec![],
+        }
+    }
+
+    fn zone_farmland(&mut self) -> Vec<Command> {
+        match self.world_coord {
+            Some(world_coord) => {
+                let position = v2(world_coord.x as usize, world_coord.y as usize);
+                if !self.world.territory().can_build(&position, Owner::Player) {
+                    println!("Cannot farm land owned by others");
+                    return vec![];
+                }
+                let occupied = self.house_builder.houses()[(position.x, position.y)]
+                    || self.harvesters.contains(&position);
+                if self.farms.zone(&mut self.world, &position, occupied) {
+                    self.world_artist.draw_affected(&self.world, vec![position])
+                } else {
+                    println!("Land is too steep, wet or built on to farm");
+                    vec![]
+                }
+            }
+            None => vec![],
+        }
+    }
+
     fn build_house(&mut self) -> Vec<Command> {
         if let Some(world_coord) = self.world_coord {
             let world_coord = self.world.snap_middle(world_coord);
-            self.house_builder.build_house(world_coord)
+            let position = v2(world_coord.x as usize, world_coord.y as usize);
+            let mut commands =
+                self.house_builder
+                    .build_house(&self.world, world_coord, Owner::Player);
+            if commands.is_empty() {
+                return commands;
+            }
+            let territory = self.world.territory_mut();
+            let changed = if self.house_builder.houses()[(position.x, position.y)] {
+                self.population.settle(&position, 2.0);
+                territory.claim(&position, 2, Owner::Player)
+            } else {
+                self.population.remove(&position);
+                territory.release(&position, 2, Owner::Player)
+            };
+            commands.append(&mut self.world_artist.draw_affected(&self.world, changed));
+            commands
         } else {
             vec![]
         }
     }
 }
 
+impl Drop for GameHandler {
+    fn drop(&mut self) {
+        self.save_exploration();
+    }
+}
+
 impl EventHandler for GameHandler {
     fn handle_event(&mut self, event: Arc<Event>) -> Vec<Command> {
         let label_commands = self.label_editor.handle_event(event.clone());
         if !label_commands.is_empty() {
             label_commands
         } else {
-            match *event {
+            let mut commands = match *event {
                 Event::Start => self.world_artist.init(&self.world),
+                Event::Tick => self.tick(),
                 Event::WorldPositionChanged(world_coord) => {
                     self.world_coord = Some(world_coord);
-                    vec![]
+                    self.draw_travel_estimate()
                 }
                 Event::Key {
                     key,
                     state: ElementState::Pressed,
                     ..
                 } => match key {
-                    VirtualKeyCode::H => {
-                        self.avatar.reposition(self.world_coord, &self.world);
-                        self.avatar.draw()
-                    }
-                    VirtualKeyCode::W => {
-                        self.avatar.walk(&self.world);
-                        self.avatar.draw()
-                    }
+                    VirtualKeyCode::H => self.reposition_avatar(),
+                    VirtualKeyCode::U => self.add_avatar(),
+                    VirtualKeyCode::W => self.give_order(Order::Walk),
+                    VirtualKeyCode::G => self.go_to(),
                     VirtualKeyCode::A => {
-                        self.avatar.rotate_anticlockwise();
-                        self.avatar.draw()
+                        if let Some(avatar) = self.party.selected_mut() {
+                            avatar.rotate_anticlockwise();
+                        }
+                        self.party.draw_selected(&self.world)
                     }
                     VirtualKeyCode::D => {
-                        self.avatar.rotate_clockwise();
-                        self.avatar.draw()
+                        if let Some(avatar) = self.party.selected_mut() {
+                            avatar.rotate_clockwise();
+                        }
+                        self.party.draw_selected(&self.world)
                     }
                     VirtualKeyCode::Q => self.rotate(PI / 16.0),
                     VirtualKeyCode::E => self.rotate(-PI / 16.0),
-                    VirtualKeyCode::R => self.build_road(),
+                    VirtualKeyCode::R => self.give_order(Order::BuildRoad),
                     VirtualKeyCode::L => {
-                        if let Some(world_coord) = self.avatar.position() {
+                        if let Some(world_coord) =
+                            self.party.selected().and_then(|avatar| avatar.position())
+                        {
                             self.label_editor.start_edit(world_coord);
                         }
                         vec![]
                     }
                     VirtualKeyCode::B => self.build_house(),
+                    VirtualKeyCode::X => self.build_harvester(),
+                    VirtualKeyCode::F => self.zone_farmland(),
+                    VirtualKeyCode::Z => self.toggle_erosion(),
+                    VirtualKeyCode::O => {
+                        self.showing_viewshed = false;
+                        self.world_artist.cycle_overlay(&self.world)
+                    }
+                    VirtualKeyCode::V => self.toggle_viewshed(),
+                    VirtualKeyCode::C => self.world_artist.toggle_contours(&self.world),
+                    VirtualKeyCode::M => self.toggle_minimap(),
+                    VirtualKeyCode::P => {
+                        self.clock.toggle_pause();
+                        self.print_clock()
+                    }
+                    VirtualKeyCode::Period => {
+                        self.clock.speed_up();
+                        self.print_clock()
+                    }
+                    VirtualKeyCode::Comma => {
+                        self.clock.slow_down();
+                        self.print_clock()
+                    }
+                    VirtualKeyCode::N => self.step(),
                     _ => vec![],
                 },
+                Event::Button {
+                    button: MouseButton::Left,
+                    state: ElementState::Pressed,
+                    ..
+                } => self.jump_to(),
                 _ => vec![],
-            }
+            };
+            self.minimap.observe(&commands);
+            commands.append(&mut self.draw_minimap());
+            commands
         }
     }
 }
@@ -1,43 +1,162 @@
+use crate::sun::tint;
+use crate::territory::Owner;
+use crate::world::World;
 use isometric::coords::WorldCoord;
 use isometric::drawing::HouseDrawing;
 use isometric::Color;
 use isometric::Command;
-use isometric::{M, V3};
+use isometric::{v2, M, V2, V3};
 
 pub struct HouseBuilder {
     houses: M<bool>,
+    abandoned: M<bool>,
+    pending: Vec<V2<usize>>,
+    version: u64,
     light_direction: V3<f32>,
     color: Color,
+    tint: Color,
 }
 
 impl HouseBuilder {
-    pub fn new(width: usize, height: usize, light_direction: V3<f32>) -> HouseBuilder {
+    const ABANDONED: Color = Color {
+        r: 0.4,
+        g: 0.4,
+        b: 0.4,
+        a: 1.0,
+    };
+
+    pub fn new(
+        width: usize,
+        height: usize,
+        light_direction: V3<f32>,
+        color: Color,
+    ) -> HouseBuilder {
         HouseBuilder {
             houses: M::from_element(width, height, false),
+            abandoned: M::from_element(width, height, false),
+            pending: vec![],
+            version: 0,
             light_direction,
-            color: Color::new(1.0, 0.0, 0.0, 1.0),
+            color,
+            tint: Color::new(1.0, 1.0, 1.0, 1.0),
         }
     }
 
-    pub fn build_house(&mut self, world_coord: WorldCoord) -> Vec<Command> {
+    pub fn houses(&self) -> &M<bool> {
+        &self.houses
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn get_name(index: (usize, usize)) -> String {
+        format!("house-{:?}", index)
+    }
+
+    fn draw_house(&self, world_coord: WorldCoord) -> Command {
         let index = (world_coord.x as usize, world_coord.y as usize);
+        let color = if self.abandoned[index] {
+            HouseBuilder::ABANDONED
+        } else {
+            self.color
+        };
+        let drawing = HouseDrawing::new(
+            world_coord,
+            0.25,
+            0.5,
+            0.5,
+            tint(&color, &self.tint),
+            self.light_direction,
+        );
+        Command::Draw {
+            name: HouseBuilder::get_name(index),
+            drawing: Box::new(drawing),
+        }
+    }
+
+    pub fn build_house(
+        &mut self,
+        world: &World,
+        world_coord: WorldCoord,
+        owner: Owner,
+    ) -> Vec<Command> {
+        let index = (world_coord.x as usize, world_coord.y as usize);
+        if !world.territory().can_build(&v2(index.0, index.1), owner) {
+            println!("Cannot build house on land owned by others");
+            return vec![];
+        }
+        self.version += 1;
         self.houses[index] = !self.houses[index];
-        let name = format!("house-{:?}", index);
+        self.abandoned[index] = false;
         if self.houses[index] {
-            let drawing = HouseDrawing::new(
-                world_coord,
-                0.25,
-                0.5,
-                0.5,
-                self.color,
-                self.light_direction,
-            );
-            vec![Command::Draw {
-                name,
-                drawing: Box::new(drawing),
-            }]
+            vec![self.draw_house(world_coord)]
         } else {
-            vec![Command::Erase(name)]
+            vec![Command::Erase(HouseBuilder::get_name(index))]
+        }
+    }
+
+    fn get_world_coord(world: &World, position: &V2<usize>) -> WorldCoord {
+        world.snap_middle(WorldCoord::new(position.x as f32, position.y as f32, 0.0))
+    }
+
+    pub fn add_house(&mut self, world: &World, position: &V2<usize>) -> Vec<Command> {
+        let index = (position.x, position.y);
+        self.version += 1;
+        self.houses[index] = true;
+        self.abandoned[index] = false;
+        vec![self.draw_house(HouseBuilder::get_world_coord(world, position))]
+    }
+
+    pub fn remove_house(&mut self, position: &V2<usize>) -> Vec<Command> {
+        let index = (position.x, position.y);
+        self.version += 1;
+        self.houses[index] = false;
+        self.abandoned[index] = false;
+        vec![Command::Erase(HouseBuilder::get_name(index))]
+    }
+
+    pub fn set_abandoned(
+        &mut self,
+        world: &World,
+        position: &V2<usize>,
+        abandoned: bool,
+    ) -> Vec<Command> {
+        self.abandoned[(position.x, position.y)] = abandoned;
+        vec![self.draw_house(HouseBuilder::get_world_coord(world, position))]
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+        self.pending = self.get_all_houses();
+    }
+
+    pub fn set_light(&mut self, light_direction: V3<f32>, tint: Color) {
+        self.light_direction = light_direction;
+        self.tint = tint;
+        self.pending = self.get_all_houses();
+    }
+
+    pub fn draw_pending(&mut self, world: &World, max_houses: usize) -> Vec<Command> {
+        let split = self.pending.len().saturating_sub(max_houses);
+        self.pending
+            .split_off(split)
+            .into_iter()
+            .filter(|position| self.houses[(position.x, position.y)])
+            .map(|position| self.draw_house(HouseBuilder::get_world_coord(world, &position)))
+            .collect()
+    }
+
+    fn get_all_houses(&self) -> Vec<V2<usize>> {
+        let (width, height) = self.houses.shape();
+        let mut out = vec![];
+        for x in 0..width {
+            for y in 0..height {
+                if self.houses[(x, y)] {
+                    out.push(v2(x, y));
+                }
+            }
         }
+        out
     }
 }
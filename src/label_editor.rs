@@ -12,6 +12,7 @@ use std::sync::Arc;
 pub struct LabelEditor {
     font: Arc<Font>,
     edit: Option<LabelEdit>,
+    labels: Vec<WorldCoord>,
 }
 
 impl LabelEditor {
@@ -22,9 +23,14 @@ impl LabelEditor {
                 Texture::new(image::open("serif.png").unwrap()),
             )),
             edit: None,
+            labels: vec![],
         }
     }
 
+    pub fn labels(&self) -> &Vec<WorldCoord> {
+        &self.labels
+    }
+
     pub fn start_edit(&mut self, world_coord: WorldCoord) {
         self.edit = Some(LabelEdit::new(self.font.clone(), world_coord));
     }
@@ -39,9 +45,25 @@ impl EventHandler for LabelEditor {
                     state: ElementState::Pressed,
                     ..
                 } => {
+                    if !self.labels.contains(&edit.world_coord) {
+                        self.labels.push(edit.world_coord);
+                    }
                     self.edit = None;
                     vec![]
                 }
+                Event::Key {
+                    key: VirtualKeyCode::Escape,
+                    state: ElementState::Pressed,
+                    ..
+                } => {
+                    let commands = if self.labels.contains(&edit.world_coord) {
+                        vec![]
+                    } else {
+                        vec![Command::Erase(edit.get_name())]
+                    };
+                    self.edit = None;
+                    commands
+                }
                 _ => edit.handle_event(event),
             }
         } else {
@@ -64,14 +86,17 @@ impl LabelEdit {
             text_editor: TextEditor::new(),
         }
     }
+
+    fn get_name(&self) -> String {
+        format!("{:?}", self.world_coord)
+    }
 }
 
 impl EventHandler for LabelEdit {
     fn handle_event(&mut self, event: Arc<Event>) -> Vec<Command> {
         self.text_editor.handle_event(event.clone());
-        let name = format!("{:?}", self.world_coord);
         vec![Command::Draw {
-            name,
+            name: self.get_name(),
             drawing: Box::new(Text::new(
                 &self.text_editor.text(),
                 self.world_coord,
@@ -0,0 +1,205 @@
+use crate::utils::float_ordering;
+use isometric::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Debug, PartialEq)]
+pub struct Lake {
+    level: f32,
+    spill_point: V2<usize>,
+    positions: Vec<V2<usize>>,
+}
+
+impl Lake {
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn spill_point(&self) -> &V2<usize> {
+        &self.spill_point
+    }
+
+    pub fn positions(&self) -> &Vec<V2<usize>> {
+        &self.positions
+    }
+}
+
+#[derive(PartialEq)]
+struct Fill {
+    position: V2<usize>,
+    level: f32,
+}
+
+impl Eq for Fill {}
+
+impl Ord for Fill {
+    fn cmp(&self, other: &Fill) -> Ordering {
+        float_ordering(&other.level, &self.level)
+    }
+}
+
+impl PartialOrd for Fill {
+    fn partial_cmp(&self, other: &Fill) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn get_neighbours(position: &V2<usize>, width: usize, height: usize) -> Vec<V2<usize>> {
+    let mut out = vec![];
+    if position.x > 0 {
+        out.push(v2(position.x - 1, position.y));
+    }
+    if position.x + 1 < width {
+        out.push(v2(position.x + 1, position.y));
+    }
+    if position.y > 0 {
+        out.push(v2(position.x, position.y - 1));
+    }
+    if position.y + 1 < height {
+        out.push(v2(position.x, position.y + 1));
+    }
+    out
+}
+
+pub fn fill_depressions(elevations: &M<f32>, sea_level: f32) -> M<f32> {
+    let (width, height) = elevations.shape();
+    let mut filled = elevations.clone();
+    let mut visited = M::from_element(width, height, false);
+    let mut queue = BinaryHeap::new();
+    for x in 0..width {
+        for y in 0..height {
+            let on_border = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+            if on_border || elevations[(x, y)] <= sea_level {
+                visited[(x, y)] = true;
+                queue.push(Fill {
+                    position: v2(x, y),
+                    level: elevations[(x, y)],
+                });
+            }
+        }
+    }
+    while let Some(Fill { position, level }) = queue.pop() {
+        for neighbour in get_neighbours(&position, width, height) {
+            let index = (neighbour.x, neighbour.y);
+            if !visited[index] {
+                visited[index] = true;
+                filled[index] = filled[index].max(level);
+                queue.push(Fill {
+                    position: neighbour,
+                    level: filled[index],
+                });
+            }
+        }
+    }
+    filled
+}
+
+fn is_flooded(elevations: &M<f32>, filled: &M<f32>, position: &V2<usize>) -> bool {
+    let index = (position.x, position.y);
+    filled[index] > elevations[index]
+}
+
+fn get_lake(
+    elevations: &M<f32>,
+    filled: &M<f32>,
+    assigned: &mut M<bool>,
+    start: V2<usize>,
+) -> Lake {
+    let (width, height) = elevations.shape();
+    let level = filled[(start.x, start.y)];
+    let mut positions = vec![];
+    let mut rim = vec![];
+    let mut stack = vec![start];
+    assigned[(start.x, start.y)] = true;
+    while let Some(position) = stack.pop() {
+        positions.push(position);
+        for neighbour in get_neighbours(&position, width, height) {
+            if is_flooded(elevations, filled, &neighbour) {
+                if !assigned[(neighbour.x, neighbour.y)] {
+                    assigned[(neighbour.x, neighbour.y)] = true;
+                    stack.push(neighbour);
+                }
+            } else {
+                rim.push(neighbour);
+            }
+        }
+    }
+    let spill_point = rim
+        .into_iter()
+        .min_by(|a, b| float_ordering(&elevations[(a.x, a.y)], &elevations[(b.x, b.y)]))
+        .unwrap();
+    Lake {
+        level,
+        spill_point,
+        positions,
+    }
+}
+
+pub fn get_lakes(elevations: &M<f32>, sea_level: f32) -> Vec<Lake> {
+    let filled = fill_depressions(elevations, sea_level);
+    let (width, height) = elevations.shape();
+    let mut assigned = M::from_element(width, height, false);
+    let mut out = vec![];
+    for x in 0..width {
+        for y in 0..height {
+            let position = v2(x, y);
+            if !assigned[(x, y)] && is_flooded(elevations, &filled, &position) {
+                out.push(get_lake(elevations, &filled, &mut assigned, position));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[rustfmt::skip]
+    fn elevations() -> M<f32> {
+        M::from_vec(5, 5, vec![
+            3.0, 3.0, 3.0, 3.0, 3.0,
+            3.0, 1.0, 3.0, 3.0, 3.0,
+            3.0, 3.0, 3.0, 2.0, 2.0,
+            3.0, 3.0, 1.0, 1.5, 3.0,
+            3.0, 3.0, 3.0, 3.0, 0.0,
+        ])
+    }
+
+    #[rustfmt::skip]
+    #[test]
+    fn test_fill_depressions() {
+        let expected = M::from_vec(5, 5, vec![
+            3.0, 3.0, 3.0, 3.0, 3.0,
+            3.0, 3.0, 3.0, 3.0, 3.0,
+            3.0, 3.0, 3.0, 2.0, 2.0,
+            3.0, 3.0, 2.0, 2.0, 3.0,
+            3.0, 3.0, 3.0, 3.0, 0.0,
+        ]);
+        assert_eq!(fill_depressions(&elevations(), 0.5), expected);
+    }
+
+    #[test]
+    fn test_below_sea_level_is_not_filled() {
+        let mut elevations = M::from_element(3, 3, 1.0);
+        elevations[(1, 1)] = 0.0;
+        assert_eq!(fill_depressions(&elevations, 0.5), elevations);
+    }
+
+    #[test]
+    fn test_get_lakes() {
+        let lakes = get_lakes(&elevations(), 0.5);
+        assert_eq!(lakes.len(), 2);
+
+        assert_eq!(lakes[0].level(), 3.0);
+        assert_eq!(lakes[0].positions(), &vec![v2(1, 1)]);
+        assert_eq!(lakes[0].spill_point(), &v2(0, 1));
+
+        assert_eq!(lakes[1].level(), 2.0);
+        assert_eq!(lakes[1].positions().len(), 2);
+        assert!(lakes[1].positions().contains(&v2(2, 3)));
+        assert!(lakes[1].positions().contains(&v2(3, 3)));
+        assert_eq!(lakes[1].spill_point(), &v2(3, 2));
+    }
+}
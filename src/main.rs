@@ -1,10 +1,30 @@
 extern crate nalgebra as na;
 
 mod avatar;
+mod climate;
+mod clock;
+mod contours;
+mod economy;
+mod erosion;
+mod exploration;
+mod farming;
+mod flood;
 mod game_handler;
 mod house_builder;
 mod label_editor;
+mod lakes;
+mod minimap;
+mod overlay;
+mod palette;
+mod party;
+mod population;
+mod resources;
 mod roadset;
+mod sun;
+mod territory;
+mod tide;
+mod traffic;
+mod travel;
 mod utils;
 mod world;
 mod world_artist;
@@ -19,10 +39,17 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     let size = args[1].parse().unwrap();
     let seed = args[2].parse().unwrap();
-    let world = generate_world(size, seed);
+    let cliff_gradient = 0.53;
+    let world = generate_world(size, seed, cliff_gradient);
+    let exploration_path = format!("explored-{}-{}.txt", size, seed);
 
     let mut engine = IsometricEngine::new("Frontier", 1024, 1024, world.max_height());
-    engine.add_event_handler(Box::new(GameHandler::new(world)));
+    engine.add_event_handler(Box::new(GameHandler::new(
+        world,
+        exploration_path,
+        seed,
+        cliff_gradient,
+    )));
 
     engine.run();
 }
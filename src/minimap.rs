@@ -0,0 +1,445 @@
+use crate::palette::Palette;
+use crate::world::World;
+use image::{DynamicImage, Rgba, RgbaImage};
+use isometric::coords::WorldCoord;
+use isometric::drawing::Billboard;
+use isometric::*;
+use std::sync::Arc;
+
+pub struct MinimapFeatures<'a> {
+    pub version: u64,
+    pub colors: &'a M<Color>,
+    pub palette: &'a Palette,
+    pub houses: &'a M<bool>,
+    pub labels: &'a Vec<WorldCoord>,
+    pub avatar: Option<WorldCoord>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct Markers {
+    avatar: Option<(u32, u32)>,
+    focus: Option<WorldCoord>,
+    yaw: f32,
+}
+
+pub struct Minimap {
+    size: u32,
+    scale: f32,
+    view_size: f32,
+    extent: f32,
+    height: f32,
+    focus: Option<WorldCoord>,
+    yaw: f32,
+    visible: bool,
+    base: Option<(u64, RgbaImage)>,
+    drawn: Option<Markers>,
+}
+
+impl Minimap {
+    pub fn new(world: &World, size: u32, view_size: f32) -> Minimap {
+        let scale = world.width().max(world.height()) as f32 / size as f32;
+        let extent = world.width().max(world.height()) as f32 / 4.0;
+        Minimap {
+            size,
+            scale,
+            view_size,
+            extent,
+            height: world.max_height(),
+            focus: None,
+            yaw: 0.0,
+            visible: false,
+            base: None,
+            drawn: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn observe(&mut self, commands: &[Command]) {
+        for command in commands {
+            match command {
+                Command::LookAt(world_coord) => self.focus = Some(*world_coord),
+                Command::Rotate { yaw, .. } => self.yaw += yaw,
+                _ => (),
+            }
+        }
+    }
+
+    fn rotate(&self, x: f32, y: f32) -> (f32, f32) {
+        let (sin, cos) = self.yaw.sin_cos();
+        (x * cos - y * sin, x * sin + y * cos)
+    }
+
+    fn unrotate(&self, x: f32, y: f32) -> (f32, f32) {
+        let (sin, cos) = self.yaw.sin_cos();
+        (x * cos + y * sin, y * cos - x * sin)
+    }
+
+    fn anchor(&self) -> WorldCoord {
+        let (x, y) = self.focus.map_or((0.0, 0.0), |focus| (focus.x, focus.y));
+        let (dx, dy) = self.rotate(-self.extent, -self.extent);
+        WorldCoord::new(x + dx, y + dy, self.height)
+    }
+
+    fn to_position(&self, x: u32, y: u32) -> V2<usize> {
+        v2(
+            (x as f32 * self.scale) as usize,
+            (y as f32 * self.scale) as usize,
+        )
+    }
+
+    fn to_pixel(&self, world_coord: &WorldCoord) -> Option<(u32, u32)> {
+        let x = (world_coord.x / self.scale).floor();
+        let y = (world_coord.y / self.scale).floor();
+        if x >= 0.0 && y >= 0.0 && x < self.size as f32 && y < self.size as f32 {
+            Some((x as u32, y as u32))
+        } else {
+            None
+        }
+    }
+
+    fn to_rgba(color: &Color) -> Rgba<u8> {
+        Rgba([
+            (color.r * 255.0) as u8,
+            (color.g * 255.0) as u8,
+            (color.b * 255.0) as u8,
+            255,
+        ])
+    }
+
+    fn get_tile_color(
+        &self,
+        world: &World,
+        features: &MinimapFeatures,
+        position: &V2<usize>,
+    ) -> Color {
+        let (width, height) = features.colors.shape();
+        let road = world.roads().get_node(*position);
+        let river = world.rivers().get_node(*position);
+        if !world.is_explored(position) {
+            features.palette.unexplored
+        } else if road.width() > 0.0 || road.height() > 0.0 {
+            features.palette.road
+        } else if river.width() > 0.0 || river.height() > 0.0 {
+            features.palette.river
+        } else if world.is_sea(position) {
+            features.palette.sea
+        } else {
+            features.colors[(position.x.min(width - 1), position.y.min(height - 1))]
+        }
+    }
+
+    fn draw_marker(&self, image: &mut RgbaImage, world_coord: &WorldCoord, color: Rgba<u8>) {
+        if let Some((x, y)) = self.to_pixel(world_coord) {
+            for dx in 0..2 {
+                for dy in 0..2 {
+                    if x + dx < self.size && y + dy < self.size {
+                        image.put_pixel(x + dx, y + dy, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_view(&self, image: &mut RgbaImage, focus: &WorldCoord, color: Rgba<u8>) {
+        let half = self.view_size / 2.0;
+        let corners: Vec<(f32, f32)> = [(-half, -half), (half, -half), (half, half), (-half, half)]
+            .iter()
+            .map(|(x, y)| {
+                let (dx, dy) = self.rotate(*x, *y);
+                (focus.x + dx, focus.y + dy)
+            })
+            .collect();
+        for (index, from) in corners.iter().enumerate() {
+            let to = corners[(index + 1) % corners.len()];
+            let steps = ((to.0 - from.0).abs().max((to.1 - from.1).abs()) / self.scale).ceil();
+            for step in 0..=steps as usize {
+                let t = if steps > 0.0 {
+                    step as f32 / steps
+                } else {
+                    0.0
+                };
+                let point = WorldCoord::new(
+                    from.0 + (to.0 - from.0) * t,
+                    from.1 + (to.1 - from.1) * t,
+                    0.0,
+                );
+                if let Some((x, y)) = self.to_pixel(&point) {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    fn get_base(&self, world: &World, features: &MinimapFeatures) -> RgbaImage {
+        let mut image = RgbaImage::new(self.size, self.size);
+        for x in 0..self.size {
+            for y in 0..self.size {
+                let position = self.to_position(x, y);
+                if world.in_bounds(&position) {
+                    let color = self.get_tile_color(world, features, &position);
+                    image.put_pixel(x, y, Minimap::to_rgba(&color));
+                }
+            }
+        }
+        let (width, height) = features.houses.shape();
+        let house_color = Minimap::to_rgba(&features.palette.house);
+        for x in 0..width {
+            for y in 0..height {
+                if features.houses[(x, y)] {
+                    let house = WorldCoord::new(x as f32, y as f32, 0.0);
+                    self.draw_marker(&mut image, &house, house_color);
+                }
+            }
+        }
+        for label in features.labels {
+            self.draw_marker(&mut image, label, Rgba([255, 255, 255, 255]));
+        }
+        image
+    }
+
+    fn get_markers(&self, features: &MinimapFeatures) -> Markers {
+        Markers {
+            avatar: features.avatar.and_then(|avatar| self.to_pixel(&avatar)),
+            focus: self.focus,
+            yaw: self.yaw,
+        }
+    }
+
+    fn get_image(&mut self, world: &World, features: &MinimapFeatures) -> RgbaImage {
+        let stale = match self.base {
+            Some((version, _)) => version != features.version,
+            None => true,
+        };
+        if stale {
+            self.base = Some((features.version, self.get_base(world, features)));
+        }
+        let mut image = self.base.as_ref().unwrap().1.clone();
+        if let Some(focus) = self.focus {
+            self.draw_view(&mut image, &focus, Rgba([255, 255, 0, 255]));
+        }
+        if let Some(avatar) = features.avatar {
+            self.draw_marker(&mut image, &avatar, Rgba([255, 0, 255, 255]));
+        }
+        image
+    }
+
+    pub fn draw(&mut self, world: &World, features: &MinimapFeatures) -> Vec<Command> {
+        if !self.visible {
+            return match self.drawn.take() {
+                Some(_) => vec![Command::Erase("minimap".to_string())],
+                None => vec![],
+            };
+        }
+        let markers = self.get_markers(features);
+        let unchanged = self.base.as_ref().map(|(version, _)| *version) == Some(features.version)
+            && self.drawn == Some(markers);
+        if unchanged {
+            return vec![];
+        }
+        self.drawn = Some(markers);
+        let image = self.get_image(world, features);
+        let texture = Texture::new(DynamicImage::ImageRgba8(image));
+        vec![Command::Draw {
+            name: "minimap".to_string(),
+            drawing: Box::new(Billboard::new(
+                self.anchor(),
+                self.extent,
+                self.extent,
+                Arc::new(texture),
+            )),
+        }]
+    }
+
+    pub fn get_target(&self, world: &World, world_coord: WorldCoord) -> Option<WorldCoord> {
+        if !self.visible {
+            return None;
+        }
+        let half = self.extent / 2.0;
+        let anchor = self.anchor();
+        let (dx, dy) = self.unrotate(world_coord.x - anchor.x, world_coord.y - anchor.y);
+        let fx = (dx + half) / self.extent;
+        let fy = (dy + half) / self.extent;
+        if fx < 0.0 || fy < 0.0 || fx >= 1.0 || fy >= 1.0 {
+            return None;
+        }
+        let size = self.size as f32 * self.scale;
+        let target = WorldCoord::new(fx * size, fy * size, 0.0);
+        if world.in_bounds(&v2(target.x.round() as usize, target.y.round() as usize)) {
+            Some(world.snap(target))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use isometric::coords::GLCoord4D;
+    use isometric::terrain::*;
+
+    fn world() -> World {
+        let mut world = World::new(
+            M::from_element(8, 8, 1.0),
+            vec![Node::new(v2(0, 4), 0.0, 0.1), Node::new(v2(1, 4), 0.0, 0.1)],
+            vec![Edge::new(v2(0, 4), v2(1, 4))],
+            vec![],
+            0.5,
+        );
+        world.add_road(&Edge::new(v2(4, 0), v2(4, 1)));
+        world.set_explored(M::from_element(8, 8, true));
+        world
+    }
+
+    fn get_image(features: &MinimapFeatures) -> RgbaImage {
+        let world = world();
+        Minimap::new(&world, 4, 4.0).get_image(&world, features)
+    }
+
+    #[test]
+    fn test_terrain_rivers_and_roads() {
+        let colors = M::from_element(7, 7, Color::new(0.0, 1.0, 0.0, 1.0));
+        let image = get_image(&MinimapFeatures {
+            version: 0,
+            colors: &colors,
+            palette: &Palette::standard(),
+            houses: &M::from_element(8, 8, false),
+            labels: &vec![],
+            avatar: None,
+        });
+        assert_eq!(image.get_pixel(1, 1), &Rgba([0, 255, 0, 255]));
+        assert_eq!(image.get_pixel(0, 2), &Rgba([0, 0, 255, 255]));
+        assert_eq!(image.get_pixel(2, 0), &Rgba([127, 127, 127, 255]));
+    }
+
+    #[test]
+    fn test_houses_labels_and_avatar() {
+        let colors = M::from_element(7, 7, Color::new(0.0, 1.0, 0.0, 1.0));
+        let mut houses = M::from_element(8, 8, false);
+        houses[(2, 2)] = true;
+        let palette = Palette::colorblind();
+        let image = get_image(&MinimapFeatures {
+            version: 0,
+            colors: &colors,
+            palette: &palette,
+            houses: &houses,
+            labels: &vec![WorldCoord::new(6.0, 0.0, 1.0)],
+            avatar: Some(WorldCoord::new(6.0, 6.0, 1.0)),
+        });
+        assert_eq!(image.get_pixel(1, 1), &Minimap::to_rgba(&palette.house));
+        assert_eq!(image.get_pixel(3, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(image.get_pixel(3, 3), &Rgba([255, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_view() {
+        let colors = M::from_element(7, 7, Color::new(0.0, 1.0, 0.0, 1.0));
+        let world = world();
+        let mut minimap = Minimap::new(&world, 4, 4.0);
+        minimap.observe(&[Command::LookAt(WorldCoord::new(4.0, 4.0, 1.0))]);
+        let image = minimap.get_image(
+            &world,
+            &MinimapFeatures {
+                version: 0,
+                colors: &colors,
+                palette: &Palette::standard(),
+                houses: &M::from_element(8, 8, false),
+                labels: &vec![],
+                avatar: None,
+            },
+        );
+        assert_eq!(image.get_pixel(1, 1), &Rgba([255, 255, 0, 255]));
+        assert_eq!(image.get_pixel(3, 1), &Rgba([255, 255, 0, 255]));
+        assert_eq!(image.get_pixel(1, 3), &Rgba([255, 255, 0, 255]));
+        assert_eq!(image.get_pixel(3, 3), &Rgba([255, 255, 0, 255]));
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0, 255, 0, 255]));
+    }
+
+    #[test]
+    fn test_get_target() {
+        let world = world();
+        let mut minimap = Minimap::new(&world, 4, 4.0);
+        assert_eq!(
+            minimap.get_target(&world, WorldCoord::new(-2.0, -2.0, 0.0)),
+            None
+        );
+        minimap.toggle();
+        assert_eq!(
+            minimap.get_target(&world, WorldCoord::new(-2.0, -2.0, 0.0)),
+            Some(WorldCoord::new(4.0, 4.0, 1.0))
+        );
+        assert_eq!(
+            minimap.get_target(&world, WorldCoord::new(-3.0, -2.0, 0.0)),
+            Some(WorldCoord::new(0.0, 4.0, 1.0))
+        );
+        assert_eq!(
+            minimap.get_target(&world, WorldCoord::new(1.0, 1.0, 0.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get_target_after_rotation() {
+        let world = world();
+        let mut minimap = Minimap::new(&world, 4, 4.0);
+        minimap.toggle();
+        minimap.observe(&[Command::Rotate {
+            center: GLCoord4D::new(0.0, 0.0, 0.0, 1.0),
+            yaw: std::f32::consts::PI / 2.0,
+        }]);
+        assert_eq!(
+            minimap.get_target(&world, WorldCoord::new(2.0, -2.0, 0.0)),
+            Some(WorldCoord::new(4.0, 4.0, 1.0))
+        );
+        assert_eq!(
+            minimap.get_target(&world, WorldCoord::new(2.0, -2.5, 0.0)),
+            Some(WorldCoord::new(2.0, 4.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_redraw_only_on_change() {
+        let world = world();
+        let colors = M::from_element(7, 7, Color::new(0.0, 1.0, 0.0, 1.0));
+        let palette = Palette::standard();
+        let houses = M::from_element(8, 8, false);
+        let labels = vec![];
+        let mut features = MinimapFeatures {
+            version: 0,
+            colors: &colors,
+            palette: &palette,
+            houses: &houses,
+            labels: &labels,
+            avatar: None,
+        };
+        let mut minimap = Minimap::new(&world, 4, 4.0);
+        assert!(minimap.draw(&world, &features).is_empty());
+        minimap.toggle();
+        assert_eq!(minimap.draw(&world, &features).len(), 1);
+        assert!(minimap.draw(&world, &features).is_empty());
+        features.avatar = Some(WorldCoord::new(1.0, 1.0, 1.0));
+        assert_eq!(minimap.draw(&world, &features).len(), 1);
+        features.avatar = Some(WorldCoord::new(1.5, 1.0, 1.0));
+        assert!(minimap.draw(&world, &features).is_empty());
+        minimap.observe(&[Command::LookAt(WorldCoord::new(4.0, 4.0, 1.0))]);
+        assert_eq!(minimap.draw(&world, &features).len(), 1);
+        features.version = 1;
+        assert_eq!(minimap.draw(&world, &features).len(), 1);
+        minimap.toggle();
+        assert_eq!(minimap.draw(&world, &features).len(), 1);
+        assert!(minimap.draw(&world, &features).is_empty());
+    }
+
+    #[test]
+    fn test_anchor_follows_camera() {
+        let world = world();
+        let mut minimap = Minimap::new(&world, 4, 4.0);
+        assert_eq!(minimap.anchor(), WorldCoord::new(-2.0, -2.0, 1.0));
+        minimap.observe(&[Command::LookAt(WorldCoord::new(4.0, 4.0, 1.0))]);
+        assert_eq!(minimap.anchor(), WorldCoord::new(2.0, 2.0, 1.0));
+    }
+}
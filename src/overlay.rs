@@ -0,0 +1,294 @@
+use crate::flood::Floods;
+use crate::sun::tint;
+use crate::world::World;
+use isometric::terrain::Edge;
+use isometric::*;
+use std::collections::HashSet;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Overlay {
+    None,
+    Elevation,
+    Slope,
+    Flow,
+    Resources,
+    Traffic,
+    FloodRisk,
+}
+
+impl Overlay {
+    pub fn next(&self) -> Overlay {
+        match self {
+            Overlay::None => Overlay::Elevation,
+            Overlay::Elevation => Overlay::Slope,
+            Overlay::Slope => Overlay::Flow,
+            Overlay::Flow => Overlay::Resources,
+            Overlay::Resources => Overlay::Traffic,
+            Overlay::Traffic => Overlay::FloodRisk,
+            Overlay::FloodRisk => Overlay::None,
+        }
+    }
+
+    pub fn get_color(
+        &self,
+        world: &World,
+        position: &V2<usize>,
+        cliff_gradient: f32,
+        floods: &Floods,
+    ) -> Option<Color> {
+        let get_color = match self {
+            Overlay::None => return None,
+            Overlay::Elevation => get_elevation_color,
+            Overlay::Slope => get_slope_color,
+            Overlay::Flow => get_flow_color,
+            Overlay::Resources => get_resource_color,
+            Overlay::Traffic => get_traffic_color,
+            Overlay::FloodRisk => {
+                return Some(get_flood_risk_color(floods.get_risk(world, position)))
+            }
+        };
+        Some(get_color(world, position, cliff_gradient))
+    }
+
+    pub fn get_colors(
+        &self,
+        world: &World,
+        cliff_gradient: f32,
+        floods: &Floods,
+    ) -> Option<M<Color>> {
+        if *self == Overlay::None {
+            return None;
+        }
+        let (width, height) = world.terrain().elevations().shape();
+        Some(M::from_fn(width - 1, height - 1, |x, y| {
+            self.get_color(world, &v2(x, y), cliff_gradient, floods)
+                .unwrap()
+        }))
+    }
+}
+
+const ELEVATION_BANDS: f32 = 8.0;
+
+fn get_elevation_color(world: &World, position: &V2<usize>, _: f32) -> Color {
+    let elevation = world.get_lowest_corner(position);
+    if elevation < world.sea_level() {
+        return Color::new(0.0, 0.0, 1.0, 1.0);
+    }
+    let band = ((elevation / world.max_height()) * ELEVATION_BANDS).floor() / ELEVATION_BANDS;
+    Color::new(band, 0.5 + band * 0.5, band, 1.0)
+}
+
+fn get_slope_color(world: &World, position: &V2<usize>, cliff_gradient: f32) -> Color {
+    let slope = (world.get_max_abs_rise(position) / cliff_gradient).min(1.0);
+    Color::new(slope, 1.0 - slope, 0.0, 1.0)
+}
+
+fn get_river_width(world: &World, position: &V2<usize>) -> f32 {
+    world
+        .get_corners(position)
+        .iter()
+        .map(|corner| {
+            world
+                .rivers()
+                .get_horizontal_width(corner)
+                .max(world.rivers().get_vertical_width(corner))
+        })
+        .fold(0.0, f32::max)
+}
+
+fn get_flow_color(world: &World, position: &V2<usize>, _: f32) -> Color {
+    let flow = (get_river_width(world, position) * 2.0).min(1.0);
+    if flow > 0.0 {
+        Color::new(0.0, 1.0 - flow, 1.0, 1.0)
+    } else {
+        Color::new(0.5, 0.5, 0.5, 1.0)
+    }
+}
+
+fn get_resource_color(world: &World, position: &V2<usize>, _: f32) -> Color {
+    match world.get_deposit(position) {
+        Some(deposit) => deposit.resource.color(),
+        None => Color::new(0.5, 0.5, 0.5, 1.0),
+    }
+}
+
+const TRAFFIC_SCALE: f32 = 8.0;
+
+fn get_traffic_color(world: &World, position: &V2<usize>, _: f32) -> Color {
+    let [a, b, c, d] = world.get_corners(position);
+    let traffic = [(a, b), (a, d), (b, c), (d, c)]
+        .iter()
+        .map(|(from, to)| world.traffic().get_count(&Edge::new(*from, *to)))
+        .fold(0.0, f32::max);
+    let heat = (traffic / TRAFFIC_SCALE).min(1.0);
+    if heat > 0.0 {
+        Color::new(heat, 1.0 - heat, 0.0, 1.0)
+    } else {
+        Color::new(0.5, 0.5, 0.5, 1.0)
+    }
+}
+
+fn get_flood_risk_color(risk: f32) -> Color {
+    if risk > 0.0 {
+        Color::new(1.0 - risk, 1.0 - risk, 1.0, 1.0)
+    } else {
+        Color::new(0.5, 0.5, 0.5, 1.0)
+    }
+}
+
+const HIDDEN: Color = Color {
+    r: 0.3,
+    g: 0.3,
+    b: 0.45,
+    a: 1.0,
+};
+
+pub fn get_viewshed_color(
+    world: &World,
+    color: &Color,
+    viewshed: &HashSet<V2<usize>>,
+    position: &V2<usize>,
+) -> Color {
+    let visible = world
+        .get_corners(position)
+        .iter()
+        .any(|corner| viewshed.contains(corner));
+    if visible {
+        *color
+    } else {
+        tint(color, &HIDDEN)
+    }
+}
+
+pub fn get_viewshed_colors(
+    world: &World,
+    colors: &M<Color>,
+    viewshed: &HashSet<V2<usize>>,
+) -> M<Color> {
+    let (width, height) = colors.shape();
+    M::from_fn(width, height, |x, y| {
+        get_viewshed_color(world, &colors[(x, y)], viewshed, &v2(x, y))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::resources::*;
+    use crate::territory::Owner;
+    use isometric::terrain::*;
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        World::new(
+            M::from_vec(3, 3, vec![
+                1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0,
+                1.0, 1.0, 2.0,
+            ]),
+            vec![
+                Node::new(v2(0, 0), 0.0, 0.25),
+                Node::new(v2(1, 0), 0.0, 0.25),
+            ],
+            vec![
+                Edge::new(v2(0, 0), v2(1, 0)),
+            ],
+            vec![],
+            0.5
+        )
+    }
+
+    fn floods() -> Floods {
+        Floods::new(1.0, 0.5, 5.0)
+    }
+
+    #[test]
+    fn test_next() {
+        assert_eq!(Overlay::None.next(), Overlay::Elevation);
+        assert_eq!(Overlay::Elevation.next(), Overlay::Slope);
+        assert_eq!(Overlay::Slope.next(), Overlay::Flow);
+        assert_eq!(Overlay::Flow.next(), Overlay::Resources);
+        assert_eq!(Overlay::Resources.next(), Overlay::Traffic);
+        assert_eq!(Overlay::Traffic.next(), Overlay::FloodRisk);
+        assert_eq!(Overlay::FloodRisk.next(), Overlay::None);
+    }
+
+    #[test]
+    fn test_none_has_no_colors() {
+        assert!(Overlay::None.get_colors(&world(), 0.5, &floods()).is_none());
+    }
+
+    #[test]
+    fn test_elevation_colors() {
+        let colors = Overlay::Elevation
+            .get_colors(&world(), 0.5, &floods())
+            .unwrap();
+        assert_eq!(colors[(0, 0)], Color::new(0.5, 0.75, 0.5, 1.0));
+    }
+
+    #[test]
+    fn test_elevation_colors_below_sea_level() {
+        let mut world = world();
+        world.set_sea_level(1.5);
+        let colors = Overlay::Elevation
+            .get_colors(&world, 0.5, &floods())
+            .unwrap();
+        assert_eq!(colors[(0, 0)], Color::new(0.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_slope_colors() {
+        let colors = Overlay::Slope.get_colors(&world(), 2.0, &floods()).unwrap();
+        assert_eq!(colors[(0, 0)], Color::new(0.0, 1.0, 0.0, 1.0));
+        assert_eq!(colors[(1, 1)], Color::new(0.5, 0.5, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_flow_colors() {
+        let colors = Overlay::Flow.get_colors(&world(), 0.5, &floods()).unwrap();
+        assert_eq!(colors[(0, 0)], Color::new(0.0, 0.5, 1.0, 1.0));
+        assert_eq!(colors[(1, 1)], Color::new(0.5, 0.5, 0.5, 1.0));
+    }
+
+    #[test]
+    fn test_resource_colors() {
+        let mut world = world();
+        let mut resources = M::from_element(2, 2, None);
+        resources[(1, 0)] = Some(Deposit::new(Resource::Ore));
+        world.set_resources(resources);
+        let colors = Overlay::Resources
+            .get_colors(&world, 0.5, &floods())
+            .unwrap();
+        assert_eq!(colors[(0, 0)], Color::new(0.5, 0.5, 0.5, 1.0));
+        assert_eq!(colors[(1, 0)], Resource::Ore.color());
+    }
+
+    #[test]
+    fn test_traffic_colors() {
+        let mut world = world();
+        world.record_traffic(&v2(1, 1), &v2(1, 2), 4.0, Owner::Player);
+        let colors = Overlay::Traffic.get_colors(&world, 0.5, &floods()).unwrap();
+        assert_eq!(colors[(0, 0)], Color::new(0.5, 0.5, 0.5, 1.0));
+        assert_eq!(colors[(0, 1)], Color::new(0.5, 0.5, 0.0, 1.0));
+        assert_eq!(colors[(1, 1)], Color::new(0.5, 0.5, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_flood_risk_colors() {
+        let colors = Overlay::FloodRisk
+            .get_colors(&world(), 0.5, &floods())
+            .unwrap();
+        assert_eq!(colors[(0, 0)], Color::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(colors[(1, 1)], Color::new(0.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_viewshed_colors() {
+        let colors = M::from_element(2, 2, Color::new(1.0, 1.0, 1.0, 1.0));
+        let viewshed = [v2(0, 0)].iter().cloned().collect();
+        let colors = get_viewshed_colors(&world(), &colors, &viewshed);
+        assert_eq!(colors[(0, 0)], Color::new(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(colors[(1, 1)], HIDDEN);
+    }
+}
@@ -0,0 +1,250 @@
+use isometric::Color;
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Palette {
+    pub cliff: Color,
+    pub beach: Color,
+    pub grass: Color,
+    pub lake: Color,
+    pub sea: Color,
+    pub river: Color,
+    pub road: Color,
+    pub house: Color,
+    pub harvester: Color,
+    pub contour: Color,
+    pub unexplored: Color,
+    pub border: Color,
+    pub farmland: Color,
+    pub snow: Color,
+}
+
+impl Palette {
+    pub fn standard() -> Palette {
+        Palette {
+            cliff: Color::new(0.5, 0.4, 0.3, 1.0),
+            beach: Color::new(1.0, 1.0, 0.0, 1.0),
+            grass: Color::new(0.0, 0.75, 0.0, 1.0),
+            lake: Color::new(0.0, 0.25, 1.0, 1.0),
+            sea: Color::new(0.0, 0.0, 0.5, 1.0),
+            river: Color::new(0.0, 0.0, 1.0, 1.0),
+            road: Color::new(0.5, 0.5, 0.5, 1.0),
+            house: Color::new(1.0, 0.0, 0.0, 1.0),
+            harvester: Color::new(0.6, 0.3, 0.1, 1.0),
+            contour: Color::new(0.25, 0.15, 0.05, 1.0),
+            unexplored: Color::new(0.05, 0.05, 0.05, 1.0),
+            border: Color::new(1.0, 1.0, 1.0, 1.0),
+            farmland: Color::new(0.6, 0.55, 0.2, 1.0),
+            snow: Color::new(0.95, 0.95, 1.0, 1.0),
+        }
+    }
+
+    pub fn colorblind() -> Palette {
+        Palette {
+            cliff: Color::new(0.6, 0.6, 0.6, 1.0),
+            beach: Color::new(0.94, 0.89, 0.26, 1.0),
+            grass: Color::new(0.0, 0.62, 0.45, 1.0),
+            lake: Color::new(0.34, 0.71, 0.91, 1.0),
+            sea: Color::new(0.0, 0.45, 0.7, 1.0),
+            river: Color::new(0.34, 0.71, 0.91, 1.0),
+            road: Color::new(0.0, 0.0, 0.0, 1.0),
+            house: Color::new(0.84, 0.37, 0.0, 1.0),
+            harvester: Color::new(0.8, 0.47, 0.65, 1.0),
+            contour: Color::new(0.8, 0.47, 0.65, 1.0),
+            unexplored: Color::new(0.05, 0.05, 0.05, 1.0),
+            border: Color::new(0.8, 0.47, 0.65, 1.0),
+            farmland: Color::new(0.9, 0.6, 0.0, 1.0),
+            snow: Color::new(0.95, 0.95, 1.0, 1.0),
+        }
+    }
+
+    pub fn high_contrast() -> Palette {
+        Palette {
+            cliff: Color::new(0.3, 0.3, 0.3, 1.0),
+            beach: Color::new(1.0, 1.0, 1.0, 1.0),
+            grass: Color::new(0.0, 1.0, 0.0, 1.0),
+            lake: Color::new(0.0, 1.0, 1.0, 1.0),
+            sea: Color::new(0.0, 0.0, 1.0, 1.0),
+            river: Color::new(0.0, 1.0, 1.0, 1.0),
+            road: Color::new(0.0, 0.0, 0.0, 1.0),
+            house: Color::new(1.0, 0.0, 1.0, 1.0),
+            harvester: Color::new(1.0, 0.5, 0.0, 1.0),
+            contour: Color::new(1.0, 0.0, 0.0, 1.0),
+            unexplored: Color::new(0.0, 0.0, 0.0, 1.0),
+            border: Color::new(1.0, 1.0, 0.0, 1.0),
+            farmland: Color::new(1.0, 0.5, 0.0, 1.0),
+            snow: Color::new(0.8, 0.8, 0.8, 1.0),
+        }
+    }
+
+    fn preset(name: &str) -> Result<Palette, String> {
+        match name {
+            "standard" => Ok(Palette::standard()),
+            "colorblind" => Ok(Palette::colorblind()),
+            "high_contrast" => Ok(Palette::high_contrast()),
+            _ => Err(format!("Unknown palette preset {}", name)),
+        }
+    }
+
+    fn get_color_mut(&mut self, name: &str) -> Result<&mut Color, String> {
+        match name {
+            "cliff" => Ok(&mut self.cliff),
+            "beach" => Ok(&mut self.beach),
+            "grass" => Ok(&mut self.grass),
+            "lake" => Ok(&mut self.lake),
+            "sea" => Ok(&mut self.sea),
+            "river" => Ok(&mut self.river),
+            "road" => Ok(&mut self.road),
+            "house" => Ok(&mut self.house),
+            "harvester" => Ok(&mut self.harvester),
+            "contour" => Ok(&mut self.contour),
+            "unexplored" => Ok(&mut self.unexplored),
+            "border" => Ok(&mut self.border),
+            "farmland" => Ok(&mut self.farmland),
+            "snow" => Ok(&mut self.snow),
+            _ => Err(format!("Unknown palette color {}", name)),
+        }
+    }
+
+    fn parse_color(fields: &[&str]) -> Result<Color, String> {
+        let values: Result<Vec<f32>, _> = fields.iter().map(|field| field.parse()).collect();
+        match values {
+            Ok(ref values) if values.len() == 4 => {
+                Ok(Color::new(values[0], values[1], values[2], values[3]))
+            }
+            _ => Err(format!("Invalid color {}", fields.join(","))),
+        }
+    }
+
+    pub fn from_csv(csv: &str) -> Result<Palette, String> {
+        let mut palette = Palette::standard();
+        for line in csv.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            if fields[0] == "preset" && fields.len() == 2 {
+                palette = Palette::preset(fields[1])?;
+            } else {
+                *palette.get_color_mut(fields[0])? = Palette::parse_color(&fields[1..])?;
+            }
+        }
+        Ok(palette)
+    }
+}
+
+pub struct PaletteLoader {
+    path: String,
+    modified: Option<SystemTime>,
+    interval: Duration,
+    checked: Option<Instant>,
+}
+
+impl PaletteLoader {
+    pub fn new(path: &str, interval: Duration) -> PaletteLoader {
+        PaletteLoader {
+            path: path.to_string(),
+            modified: None,
+            interval,
+            checked: None,
+        }
+    }
+
+    fn is_due(&mut self, now: Instant) -> bool {
+        match self.checked {
+            Some(checked) if now.duration_since(checked) < self.interval => false,
+            _ => {
+                self.checked = Some(now);
+                true
+            }
+        }
+    }
+
+    fn get_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    pub fn load(&mut self) -> Option<Palette> {
+        if !self.is_due(Instant::now()) {
+            return None;
+        }
+        let modified = self.get_modified();
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        match fs::read_to_string(&self.path) {
+            Ok(csv) => match Palette::from_csv(&csv) {
+                Ok(palette) => Some(palette),
+                Err(error) => {
+                    println!("Could not load palette from {}: {}", self.path, error);
+                    None
+                }
+            },
+            Err(error) => {
+                println!("Could not read palette from {}: {}", self.path, error);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_empty_csv_is_standard() {
+        assert_eq!(Palette::from_csv(""), Ok(Palette::standard()));
+    }
+
+    #[test]
+    fn test_preset() {
+        assert_eq!(
+            Palette::from_csv("preset,colorblind"),
+            Ok(Palette::colorblind())
+        );
+        assert_eq!(
+            Palette::from_csv("preset,high_contrast"),
+            Ok(Palette::high_contrast())
+        );
+    }
+
+    #[test]
+    fn test_override() {
+        let csv = "# comment\npreset,high_contrast\n\nroad, 0.1, 0.2, 0.3, 1.0\n";
+        let mut expected = Palette::high_contrast();
+        expected.road = Color::new(0.1, 0.2, 0.3, 1.0);
+        assert_eq!(Palette::from_csv(csv), Ok(expected));
+    }
+
+    #[test]
+    fn test_unknown_preset() {
+        assert!(Palette::from_csv("preset,sepia").is_err());
+    }
+
+    #[test]
+    fn test_unknown_color() {
+        assert!(Palette::from_csv("forest,0.0,1.0,0.0,1.0").is_err());
+    }
+
+    #[test]
+    fn test_loader_checks_once_per_interval() {
+        let mut loader = PaletteLoader::new("palette.csv", Duration::from_secs(1));
+        let start = Instant::now();
+        assert!(loader.is_due(start));
+        assert!(!loader.is_due(start + Duration::from_millis(500)));
+        assert!(loader.is_due(start + Duration::from_secs(1)));
+        assert!(!loader.is_due(start + Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_invalid_color() {
+        assert!(Palette::from_csv("road,0.0,1.0,0.0").is_err());
+        assert!(Palette::from_csv("road,0.0,1.0,0.0,x").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::avatar::*;
+use crate::travel::TravelCosts;
+use crate::world::World;
+use isometric::coords::WorldCoord;
+use isometric::Command;
+
+pub struct Party {
+    scale: f32,
+    costs: TravelCosts,
+    textures: AvatarTextures,
+    avatars: Vec<Avatar>,
+    selected: Option<usize>,
+}
+
+impl Party {
+    pub fn new(scale: f32, costs: TravelCosts) -> Party {
+        Party {
+            scale,
+            costs,
+            textures: AvatarTextures::load(),
+            avatars: vec![],
+            selected: None,
+        }
+    }
+
+    pub fn avatars(&self) -> &Vec<Avatar> {
+        &self.avatars
+    }
+
+    pub fn get(&self, index: usize) -> &Avatar {
+        &self.avatars[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut Avatar {
+        &mut self.avatars[index]
+    }
+
+    pub fn set_costs(&mut self, costs: TravelCosts) {
+        self.costs = costs;
+        for avatar in self.avatars.iter_mut() {
+            avatar.set_costs(costs);
+        }
+    }
+
+    pub fn selected_index(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn selected(&self) -> Option<&Avatar> {
+        self.selected.map(|index| &self.avatars[index])
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut Avatar> {
+        match self.selected {
+            Some(index) => Some(&mut self.avatars[index]),
+            None => None,
+        }
+    }
+
+    pub fn add(&mut self, world_coord: Option<WorldCoord>, world: &World) -> Vec<Command> {
+        if world_coord.is_none() {
+            return vec![];
+        }
+        let name = format!("avatar-{}", self.avatars.len());
+        let mut avatar = Avatar::new(name, self.scale, self.costs, self.textures.clone());
+        avatar.reposition(world_coord, world);
+        self.avatars.push(avatar);
+        self.selected = Some(self.avatars.len() - 1);
+        self.draw(self.avatars.len() - 1, world)
+    }
+
+    pub fn select_at(&mut self, world_coord: WorldCoord, max_distance: f32) -> Vec<Command> {
+        let positions: Vec<Option<WorldCoord>> = self
+            .avatars
+            .iter()
+            .map(|avatar| avatar.position())
+            .collect();
+        match get_nearest(&positions, &world_coord, max_distance) {
+            Some(index) => {
+                self.selected = Some(index);
+                self.look_at(index, positions[index])
+            }
+            None => vec![],
+        }
+    }
+
+    fn look_at(&self, index: usize, position: Option<WorldCoord>) -> Vec<Command> {
+        match position {
+            Some(position) if self.selected == Some(index) => vec![Command::LookAt(position)],
+            _ => vec![],
+        }
+    }
+
+    pub fn draw(&self, index: usize, world: &World) -> Vec<Command> {
+        let avatar = &self.avatars[index];
+        let mut out = avatar.draw(world);
+        out.append(&mut self.look_at(index, avatar.display_position(world)));
+        out
+    }
+
+    pub fn draw_selected(&self, world: &World) -> Vec<Command> {
+        match self.selected {
+            Some(index) => self.draw(index, world),
+            None => vec![],
+        }
+    }
+
+    pub fn draw_all(&self, world: &World) -> Vec<Command> {
+        (0..self.avatars.len())
+            .flat_map(|index| self.draw(index, world))
+            .collect()
+    }
+
+    pub fn tick(&mut self, world: &World) -> Vec<Command> {
+        let mut out = vec![];
+        for index in 0..self.avatars.len() {
+            let mut commands = self.avatars[index].tick(world);
+            if !commands.is_empty() {
+                out.append(&mut commands);
+                out.append(&mut self.look_at(index, self.avatars[index].display_position(world)));
+            }
+        }
+        out
+    }
+}
+
+fn get_nearest(
+    positions: &[Option<WorldCoord>],
+    target: &WorldCoord,
+    max_distance: f32,
+) -> Option<usize> {
+    let mut out = None;
+    let mut best = max_distance;
+    for (index, position) in positions.iter().enumerate() {
+        if let Some(position) = position {
+            let dx = position.x - target.x;
+            let dy = position.y - target.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance <= best {
+                best = distance;
+                out = Some(index);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_get_nearest() {
+        let positions = vec![
+            Some(WorldCoord::new(0.0, 0.0, 0.0)),
+            None,
+            Some(WorldCoord::new(3.0, 3.0, 0.0)),
+            Some(WorldCoord::new(4.0, 3.0, 0.0)),
+        ];
+        let target = WorldCoord::new(3.8, 3.0, 0.0);
+        assert_eq!(get_nearest(&positions, &target, 1.0), Some(3));
+    }
+
+    #[test]
+    fn test_get_nearest_too_far() {
+        let positions = vec![Some(WorldCoord::new(0.0, 0.0, 0.0))];
+        let target = WorldCoord::new(3.0, 3.0, 0.0);
+        assert_eq!(get_nearest(&positions, &target, 1.0), None);
+    }
+}
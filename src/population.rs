@@ -0,0 +1,471 @@
+use crate::resources::Harvesters;
+use crate::roadset::RoadSet;
+use crate::world::World;
+use isometric::*;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum HouseChange {
+    Built(V2<usize>, V2<usize>),
+    Abandoned(V2<usize>),
+    Resettled(V2<usize>),
+    Collapsed(V2<usize>),
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+struct House {
+    population: f32,
+    abandoned_for: Option<u32>,
+}
+
+pub struct Population {
+    houses: M<Option<House>>,
+    max_gradient: f32,
+    base_capacity: f32,
+    growth_rate: f32,
+    farm_radius: usize,
+    water_radius: usize,
+    spawn_population: f32,
+    abandon_population: f32,
+    collapse_updates: u32,
+    food_per_person: f32,
+}
+
+impl Population {
+    pub fn new(width: usize, height: usize, max_gradient: f32) -> Population {
+        Population {
+            houses: M::from_element(width, height, None),
+            max_gradient,
+            base_capacity: 10.0,
+            growth_rate: 0.1,
+            farm_radius: 2,
+            water_radius: 3,
+            spawn_population: 6.0,
+            abandon_population: 0.5,
+            collapse_updates: 10,
+            food_per_person: 0.1,
+        }
+    }
+
+    pub fn inhabited(&self) -> Vec<(V2<usize>, f32)> {
+        let (width, height) = self.houses.shape();
+        let mut out = vec![];
+        for x in 0..width {
+            for y in 0..height {
+                if let Some(house) = self.houses[(x, y)] {
+                    if house.abandoned_for.is_none() {
+                        out.push((v2(x, y), house.population));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    pub fn total(&self) -> f32 {
+        self.houses
+            .iter()
+            .flatten()
+            .map(|house| house.population)
+            .sum()
+    }
+
+    pub fn settle(&mut self, position: &V2<usize>, population: f32) {
+        self.houses[(position.x, position.y)] = Some(House {
+            population,
+            abandoned_for: None,
+        });
+    }
+
+    pub fn damage(&mut self, position: &V2<usize>, fraction: f32) {
+        if let Some(house) = self.houses[(position.x, position.y)].as_mut() {
+            house.population *= 1.0 - fraction;
+        }
+    }
+
+    pub fn remove(&mut self, position: &V2<usize>) {
+        self.houses[(position.x, position.y)] = None;
+    }
+
+    fn is_habitable(&self, world: &World, position: &V2<usize>) -> bool {
+        world.in_bounds(&v2(position.x + 1, position.y + 1))
+            && world.get_lowest_corner(position) >= world.sea_level()
+            && !world.is_lake(position)
+            && world.get_max_abs_rise(position) < self.max_gradient
+    }
+
+    fn get_nearby(world: &World, position: &V2<usize>, radius: usize) -> Vec<V2<usize>> {
+        let mut out = vec![];
+        for x in position.x.saturating_sub(radius)..=position.x + radius {
+            for y in position.y.saturating_sub(radius)..=position.y + radius {
+                if x + 1 < world.width() && y + 1 < world.height() {
+                    out.push(v2(x, y));
+                }
+            }
+        }
+        out
+    }
+
+    fn get_farmland(&self, world: &World, position: &V2<usize>) -> f32 {
+        let nearby = Population::get_nearby(world, position, self.farm_radius);
+        let farmed = nearby.iter().filter(|tile| world.is_farmland(tile)).count();
+        farmed as f32 / nearby.len() as f32
+    }
+
+    fn has_node(world: &World, network: &RoadSet, position: &V2<usize>) -> bool {
+        world.get_corners(position).iter().any(|corner| {
+            let node = network.get_node(*corner);
+            node.width() > 0.0 || node.height() > 0.0
+        })
+    }
+
+    fn has_water(&self, world: &World, position: &V2<usize>) -> bool {
+        Population::get_nearby(world, position, self.water_radius)
+            .iter()
+            .any(|tile| world.is_lake(tile) || Population::has_node(world, world.rivers(), tile))
+    }
+
+    fn has_road(world: &World, position: &V2<usize>) -> bool {
+        Population::has_node(world, world.roads(), position)
+    }
+
+    fn is_free(&self, world: &World, harvesters: &Harvesters, site: &V2<usize>) -> bool {
+        self.houses[(site.x, site.y)].is_none()
+            && !world.is_farmland(site)
+            && !world.is_flooded(site)
+            && !Population::has_road(world, site)
+            && !Population::has_node(world, world.rivers(), site)
+            && !harvesters.contains(site)
+    }
+
+    pub fn get_capacity(&self, world: &World, position: &V2<usize>) -> f32 {
+        if !self.is_habitable(world, position) {
+            return 0.0;
+        }
+        let terrain = 1.0 - world.get_max_abs_rise(position) / self.max_gradient;
+        let water = if self.has_water(world, position) {
+            1.0
+        } else {
+            0.5
+        };
+        let road = if Population::has_road(world, position) {
+            1.5
+        } else {
+            1.0
+        };
+        self.base_capacity * self.get_farmland(world, position) * terrain * water * road
+    }
+
+    fn grow(&self, population: f32, capacity: f32) -> f32 {
+        if capacity <= 0.0 {
+            population * (1.0 - self.growth_rate)
+        } else {
+            population + self.growth_rate * population * (1.0 - population / capacity)
+        }
+    }
+
+    fn find_spawn_site(
+        &self,
+        world: &World,
+        harvesters: &Harvesters,
+        position: &V2<usize>,
+    ) -> Option<V2<usize>> {
+        let mut best = None;
+        let mut best_capacity = 0.0;
+        for site in Population::get_nearby(world, position, 1) {
+            let owner = world.territory().get(&site);
+            let claimable = owner.is_none() || owner == world.territory().get(position);
+            if claimable && self.is_free(world, harvesters, &site) {
+                let capacity = self.get_capacity(world, &site);
+                if capacity > best_capacity {
+                    best = Some(site);
+                    best_capacity = capacity;
+                }
+            }
+        }
+        best
+    }
+
+    pub fn food_demand(&self) -> f32 {
+        self.total() * self.food_per_person
+    }
+
+    pub fn update(
+        &mut self,
+        world: &World,
+        harvesters: &Harvesters,
+        food: f32,
+    ) -> Vec<HouseChange> {
+        let demand = self.food_demand();
+        let supply = if demand > 0.0 {
+            (food / demand).min(1.0)
+        } else {
+            1.0
+        };
+        let (width, height) = self.houses.shape();
+        let positions: Vec<V2<usize>> = (0..width)
+            .flat_map(|x| (0..height).map(move |y| v2(x, y)))
+            .filter(|position| self.houses[(position.x, position.y)].is_some())
+            .collect();
+        let mut out = vec![];
+        for position in positions {
+            if let Some(house) = self.houses[(position.x, position.y)] {
+                out.append(&mut self.update_house(world, harvesters, position, house, supply));
+            }
+        }
+        out
+    }
+
+    fn update_house(
+        &mut self,
+        world: &World,
+        harvesters: &Harvesters,
+        position: V2<usize>,
+        house: House,
+        supply: f32,
+    ) -> Vec<HouseChange> {
+        let index = (position.x, position.y);
+        let capacity = self.get_capacity(world, &position) * supply;
+        if let Some(abandoned_for) = house.abandoned_for {
+            if capacity >= self.spawn_population {
+                self.settle(&position, 1.0);
+                return vec![HouseChange::Resettled(position)];
+            } else if abandoned_for + 1 >= self.collapse_updates {
+                self.remove(&position);
+                return vec![HouseChange::Collapsed(position)];
+            }
+            self.houses[index] = Some(House {
+                population: 0.0,
+                abandoned_for: Some(abandoned_for + 1),
+            });
+            return vec![];
+        }
+        let population = self.grow(house.population, capacity);
+        if population < self.abandon_population {
+            self.houses[index] = Some(House {
+                population: 0.0,
+                abandoned_for: Some(0),
+            });
+            return vec![HouseChange::Abandoned(position)];
+        }
+        self.houses[index] = Some(House {
+            population,
+            abandoned_for: None,
+        });
+        if population >= self.spawn_population && population >= capacity * 0.8 {
+            if let Some(site) = self.find_spawn_site(world, harvesters, &position) {
+                self.settle(&position, population - 1.0);
+                self.settle(&site, 1.0);
+                return vec![HouseChange::Built(site, position)];
+            }
+        }
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::resources::*;
+    use crate::territory::Owner;
+    use isometric::terrain::*;
+
+    fn get(population: &Population, position: &V2<usize>) -> Option<f32> {
+        population.houses[(position.x, position.y)].map(|house| house.population)
+    }
+
+    fn harvesters() -> Harvesters {
+        Harvesters::new(1.0, v3(0.0, 0.0, 1.0), Color::new(1.0, 1.0, 1.0, 1.0))
+    }
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        let mut world = World::new(
+            M::from_vec(6, 6, vec![
+                1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+                0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+            ]),
+            vec![
+                Node::new(v2(0, 0), 0.2, 0.0),
+                Node::new(v2(0, 1), 0.2, 0.0),
+            ],
+            vec![
+                Edge::new(v2(0, 0), v2(0, 1)),
+            ],
+            vec![],
+            0.5
+        );
+        for x in 0..5 {
+            for y in 0..4 {
+                world.set_farmland(&v2(x, y), true);
+            }
+        }
+        world
+    }
+
+    fn spawn_world() -> World {
+        let mut world = world();
+        world.set_farmland(&v2(2, 2), false);
+        world
+    }
+
+    #[test]
+    fn test_capacity() {
+        let population = Population::new(6, 6, 0.5);
+        let world = world();
+        assert_eq!(population.get_capacity(&world, &v2(1, 1)), 10.0);
+        assert_eq!(population.get_capacity(&world, &v2(1, 4)), 0.0);
+    }
+
+    #[test]
+    fn test_capacity_without_water() {
+        let population = Population::new(6, 6, 0.5);
+        let world = world();
+        assert_eq!(population.get_capacity(&world, &v2(4, 1)), 5.0);
+    }
+
+    #[test]
+    fn test_capacity_with_road() {
+        let population = Population::new(6, 6, 0.5);
+        let mut world = world();
+        world.add_road(&Edge::new(v2(1, 1), v2(2, 1)));
+        assert_eq!(population.get_capacity(&world, &v2(1, 1)), 15.0);
+    }
+
+    #[test]
+    fn test_capacity_without_farmland() {
+        let population = Population::new(6, 6, 0.5);
+        let mut world = world();
+        for x in 0..5 {
+            world.set_farmland(&v2(x, 0), false);
+        }
+        assert_eq!(population.get_capacity(&world, &v2(1, 1)), 7.5);
+    }
+
+    #[test]
+    fn test_starvation() {
+        let mut population = Population::new(6, 6, 0.5);
+        population.settle(&v2(1, 1), 4.0);
+        assert_eq!(population.food_demand(), 0.4);
+        population.update(&world(), &harvesters(), 0.0);
+        assert!(get(&population, &v2(1, 1)).unwrap() < 4.0);
+    }
+
+    #[test]
+    fn test_spawned_houses_wait_for_next_update() {
+        let mut population = Population::new(6, 6, 0.5);
+        population.settle(&v2(1, 1), 9.0);
+        let site = match population.update(&spawn_world(), &harvesters(), 100.0)[..] {
+            [HouseChange::Built(site, _)] => site,
+            _ => panic!("Expected a new house"),
+        };
+        assert_eq!(get(&population, &site), Some(1.0));
+    }
+
+    #[test]
+    fn test_growth() {
+        let mut population = Population::new(6, 6, 0.5);
+        population.settle(&v2(1, 1), 1.0);
+        population.update(&world(), &harvesters(), 100.0);
+        assert!(get(&population, &v2(1, 1)).unwrap() > 1.0);
+    }
+
+    #[test]
+    fn test_spawn() {
+        let mut population = Population::new(6, 6, 0.5);
+        population.settle(&v2(1, 1), 9.0);
+        let changes = population.update(&spawn_world(), &harvesters(), 100.0);
+        assert_eq!(changes, vec![HouseChange::Built(v2(2, 2), v2(1, 1))]);
+        assert!(get(&population, &v2(2, 2)).is_some());
+    }
+
+    #[test]
+    fn test_no_spawn_on_farmland() {
+        let mut population = Population::new(6, 6, 0.5);
+        population.settle(&v2(1, 1), 9.0);
+        assert!(population.update(&world(), &harvesters(), 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_no_spawn_on_road() {
+        let mut population = Population::new(6, 6, 0.5);
+        let mut world = spawn_world();
+        world.add_road(&Edge::new(v2(3, 2), v2(3, 3)));
+        population.settle(&v2(1, 1), 9.0);
+        assert!(population.update(&world, &harvesters(), 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_no_spawn_on_river() {
+        let mut population = Population::new(6, 6, 0.5);
+        let mut world = world();
+        world.set_farmland(&v2(0, 0), false);
+        population.settle(&v2(1, 1), 9.0);
+        assert!(population.update(&world, &harvesters(), 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_no_spawn_on_flooded_tile() {
+        let mut population = Population::new(6, 6, 0.5);
+        let mut world = spawn_world();
+        world.set_flooded(&v2(2, 2), true);
+        population.settle(&v2(1, 1), 9.0);
+        assert!(population.update(&world, &harvesters(), 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_no_spawn_on_harvester() {
+        let mut population = Population::new(6, 6, 0.5);
+        let mut world = spawn_world();
+        let mut resources = M::from_element(6, 6, None);
+        resources[(2, 2)] = Some(Deposit::new(Resource::Timber));
+        world.set_resources(resources);
+        let mut harvesters = harvesters();
+        harvesters.build(&world, v2(2, 2));
+        population.settle(&v2(1, 1), 9.0);
+        assert!(population.update(&world, &harvesters, 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_spawn_stays_on_own_territory() {
+        let mut population = Population::new(6, 6, 0.5);
+        let mut world = spawn_world();
+        let settlement = world.territory_mut().new_settlement();
+        world.territory_mut().claim(&v2(1, 1), 0, Owner::Player);
+        world.territory_mut().claim(&v2(1, 1), 1, settlement);
+        population.settle(&v2(1, 1), 9.0);
+        assert!(population.update(&world, &harvesters(), 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_damage() {
+        let mut population = Population::new(6, 6, 0.5);
+        population.settle(&v2(1, 1), 4.0);
+        population.damage(&v2(1, 1), 0.5);
+        population.damage(&v2(2, 2), 0.5);
+        assert_eq!(get(&population, &v2(1, 1)), Some(2.0));
+        assert_eq!(get(&population, &v2(2, 2)), None);
+    }
+
+    #[test]
+    fn test_abandon_and_collapse() {
+        let mut population = Population::new(6, 6, 0.5);
+        population.settle(&v2(1, 4), 0.5);
+        assert_eq!(
+            population.update(&world(), &harvesters(), 100.0),
+            vec![HouseChange::Abandoned(v2(1, 4))]
+        );
+        for _ in 0..9 {
+            assert!(population.update(&world(), &harvesters(), 100.0).is_empty());
+        }
+        assert_eq!(
+            population.update(&world(), &harvesters(), 100.0),
+            vec![HouseChange::Collapsed(v2(1, 4))]
+        );
+        assert_eq!(get(&population, &v2(1, 4)), None);
+    }
+}
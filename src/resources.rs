@@ -0,0 +1,314 @@
+use crate::sun::tint;
+use crate::world::World;
+use isometric::coords::WorldCoord;
+use isometric::drawing::HouseDrawing;
+use isometric::*;
+use pioneer::rand::Rng;
+use std::collections::HashMap;
+
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
+pub enum Resource {
+    Timber,
+    Stone,
+    Soil,
+    Fish,
+    Ore,
+}
+
+impl Resource {
+    pub fn color(&self) -> Color {
+        match self {
+            Resource::Timber => Color::new(0.1, 0.4, 0.1, 1.0),
+            Resource::Stone => Color::new(0.6, 0.6, 0.6, 1.0),
+            Resource::Soil => Color::new(0.5, 0.35, 0.15, 1.0),
+            Resource::Fish => Color::new(0.2, 0.6, 0.9, 1.0),
+            Resource::Ore => Color::new(0.7, 0.3, 0.1, 1.0),
+        }
+    }
+
+    fn amount(&self) -> f32 {
+        match self {
+            Resource::Timber => 100.0,
+            Resource::Stone => 500.0,
+            Resource::Soil => 1000.0,
+            Resource::Fish => 200.0,
+            Resource::Ore => 300.0,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Deposit {
+    pub resource: Resource,
+    pub amount: f32,
+}
+
+impl Deposit {
+    pub fn new(resource: Resource) -> Deposit {
+        Deposit {
+            resource,
+            amount: resource.amount(),
+        }
+    }
+}
+
+fn is_river_bank(world: &World, position: &V2<usize>) -> bool {
+    world.get_corners(position).iter().any(|corner| {
+        let node = world.rivers().get_node(*corner);
+        node.width() > 0.0 || node.height() > 0.0
+    })
+}
+
+fn get_resource(
+    world: &World,
+    position: &V2<usize>,
+    cliff_gradient: f32,
+    roll: f32,
+) -> Option<Resource> {
+    let elevation = world.get_lowest_corner(position);
+    let resource = if world.is_lake(position) || elevation < world.sea_level() {
+        if roll < 0.1 {
+            Resource::Fish
+        } else {
+            return None;
+        }
+    } else if world.get_max_abs_rise(position) > cliff_gradient {
+        if roll < 0.5 {
+            Resource::Stone
+        } else {
+            return None;
+        }
+    } else if is_river_bank(world, position) {
+        if roll < 0.3 {
+            Resource::Fish
+        } else {
+            Resource::Soil
+        }
+    } else if elevation > world.max_height() * 0.6 {
+        if roll < 0.2 {
+            Resource::Ore
+        } else {
+            return None;
+        }
+    } else if elevation < world.max_height() * 0.4 && roll < 0.4 {
+        Resource::Timber
+    } else {
+        return None;
+    };
+    Some(resource)
+}
+
+pub fn get_resources<R: Rng>(
+    world: &World,
+    rng: &mut R,
+    cliff_gradient: f32,
+) -> M<Option<Deposit>> {
+    M::from_fn(world.width() - 1, world.height() - 1, |x, y| {
+        get_resource(world, &v2(x, y), cliff_gradient, rng.gen()).map(Deposit::new)
+    })
+}
+
+pub struct Harvesters {
+    harvesters: Vec<V2<usize>>,
+    rate: f32,
+    stock: HashMap<Resource, f32>,
+    pending: Vec<V2<usize>>,
+    light_direction: V3<f32>,
+    color: Color,
+    tint: Color,
+}
+
+impl Harvesters {
+    pub fn new(rate: f32, light_direction: V3<f32>, color: Color) -> Harvesters {
+        Harvesters {
+            harvesters: vec![],
+            rate,
+            stock: HashMap::new(),
+            pending: vec![],
+            light_direction,
+            color,
+            tint: Color::new(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn contains(&self, position: &V2<usize>) -> bool {
+        self.harvesters.contains(position)
+    }
+
+    pub fn stock(&self) -> &HashMap<Resource, f32> {
+        &self.stock
+    }
+
+    fn get_name(position: &V2<usize>) -> String {
+        format!("harvester-{:?}", (position.x, position.y))
+    }
+
+    fn draw(&self, world: &World, position: &V2<usize>) -> Command {
+        let world_coord =
+            world.snap_middle(WorldCoord::new(position.x as f32, position.y as f32, 0.0));
+        Command::Draw {
+            name: Harvesters::get_name(position),
+            drawing: Box::new(HouseDrawing::new(
+                world_coord,
+                0.15,
+                0.3,
+                0.3,
+                tint(&self.color, &self.tint),
+                self.light_direction,
+            )),
+        }
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+        self.pending = self.harvesters.clone();
+    }
+
+    pub fn set_light(&mut self, light_direction: V3<f32>, tint: Color) {
+        self.light_direction = light_direction;
+        self.tint = tint;
+        self.pending = self.harvesters.clone();
+    }
+
+    pub fn draw_pending(&mut self, world: &World, max_harvesters: usize) -> Vec<Command> {
+        let split = self.pending.len().saturating_sub(max_harvesters);
+        self.pending
+            .split_off(split)
+            .into_iter()
+            .filter(|position| self.harvesters.contains(position))
+            .map(|position| self.draw(world, &position))
+            .collect()
+    }
+
+    pub fn build(&mut self, world: &World, position: V2<usize>) -> Vec<Command> {
+        if self.harvesters.contains(&position) || world.get_deposit(&position).is_none() {
+            return vec![];
+        }
+        self.harvesters.push(position);
+        vec![self.draw(world, &position)]
+    }
+
+    pub fn update(&mut self, world: &mut World) -> Vec<Command> {
+        let mut out = vec![];
+        let rate = self.rate;
+        let stock = &mut self.stock;
+        self.harvesters.retain(|position| {
+            let resource = match world.get_deposit(position) {
+                Some(deposit) => deposit.resource,
+                None => return false,
+            };
+            *stock.entry(resource).or_insert(0.0) += world.harvest(position, rate);
+            if world.get_deposit(position).is_none() {
+                out.push(Command::Erase(Harvesters::get_name(position)));
+                false
+            } else {
+                true
+            }
+        });
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use isometric::terrain::*;
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        World::new(
+            M::from_vec(4, 4, vec![
+                0.0, 0.0, 0.0, 0.0,
+                1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 3.0, 10.0,
+                1.0, 1.0, 10.0, 10.0,
+            ]),
+            vec![
+                Node::new(v2(0, 1), 0.0, 0.2),
+                Node::new(v2(1, 1), 0.0, 0.2),
+            ],
+            vec![
+                Edge::new(v2(0, 1), v2(1, 1)),
+            ],
+            vec![],
+            0.5
+        )
+    }
+
+    #[test]
+    fn test_fish_in_sea() {
+        let world = world();
+        assert_eq!(
+            get_resource(&world, &v2(2, 0), 0.5, 0.05),
+            Some(Resource::Fish)
+        );
+        assert_eq!(get_resource(&world, &v2(2, 0), 0.5, 0.5), None);
+    }
+
+    #[test]
+    fn test_stone_on_cliffs() {
+        let world = world();
+        assert_eq!(
+            get_resource(&world, &v2(2, 2), 0.5, 0.4),
+            Some(Resource::Stone)
+        );
+    }
+
+    #[test]
+    fn test_river_banks() {
+        let world = world();
+        assert_eq!(
+            get_resource(&world, &v2(0, 1), 0.5, 0.1),
+            Some(Resource::Fish)
+        );
+        assert_eq!(
+            get_resource(&world, &v2(0, 1), 0.5, 0.9),
+            Some(Resource::Soil)
+        );
+    }
+
+    #[test]
+    fn test_timber_in_lowlands() {
+        let world = world();
+        assert_eq!(
+            get_resource(&world, &v2(0, 2), 0.5, 0.1),
+            Some(Resource::Timber)
+        );
+        assert_eq!(get_resource(&world, &v2(0, 2), 0.5, 0.9), None);
+    }
+
+    #[test]
+    fn test_harvest_until_depleted() {
+        let mut world = world();
+        let mut resources = M::from_element(3, 3, None);
+        resources[(0, 2)] = Some(Deposit {
+            resource: Resource::Timber,
+            amount: 15.0,
+        });
+        world.set_resources(resources);
+        let mut harvesters =
+            Harvesters::new(10.0, v3(0.0, 0.0, 1.0), Color::new(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(harvesters.build(&world, v2(0, 2)).len(), 1);
+        assert!(harvesters.build(&world, v2(1, 1)).is_empty());
+        assert!(harvesters.update(&mut world).is_empty());
+        assert_eq!(harvesters.update(&mut world).len(), 1);
+        assert_eq!(harvesters.stock()[&Resource::Timber], 15.0);
+        assert_eq!(world.get_deposit(&v2(0, 2)), None);
+    }
+
+    #[test]
+    fn test_redraw_after_light_change() {
+        let mut world = world();
+        let mut resources = M::from_element(3, 3, None);
+        resources[(0, 2)] = Some(Deposit::new(Resource::Timber));
+        world.set_resources(resources);
+        let mut harvesters =
+            Harvesters::new(10.0, v3(0.0, 0.0, 1.0), Color::new(1.0, 1.0, 1.0, 1.0));
+        harvesters.build(&world, v2(0, 2));
+        assert!(harvesters.draw_pending(&world, 64).is_empty());
+        harvesters.set_light(v3(1.0, 0.0, 1.0), Color::new(0.5, 0.5, 0.5, 1.0));
+        assert_eq!(harvesters.draw_pending(&world, 64).len(), 1);
+        assert!(harvesters.draw_pending(&world, 64).is_empty());
+    }
+}
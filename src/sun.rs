@@ -0,0 +1,170 @@
+use isometric::{Color, V3};
+use std::f32::consts::PI;
+
+pub fn tint(color: &Color, tint: &Color) -> Color {
+    Color::new(
+        color.r * tint.r,
+        color.g * tint.g,
+        color.b * tint.b,
+        color.a * tint.a,
+    )
+}
+
+pub fn lerp(from: &Color, to: &Color, t: f32) -> Color {
+    Color::new(
+        from.r + (to.r - from.r) * t,
+        from.g + (to.g - from.g) * t,
+        from.b + (to.b - from.b) * t,
+        from.a + (to.a - from.a) * t,
+    )
+}
+
+pub struct Sun {
+    day_length: u64,
+    steps: u64,
+    ticks: u64,
+}
+
+impl Sun {
+    const NIGHT: Color = Color {
+        r: 0.25,
+        g: 0.25,
+        b: 0.45,
+        a: 1.0,
+    };
+    const TWILIGHT: Color = Color {
+        r: 1.0,
+        g: 0.6,
+        b: 0.4,
+        a: 1.0,
+    };
+    const DAY: Color = Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    };
+    const TWILIGHT_HEIGHT: f32 = 0.25;
+    const MIN_LIGHT_HEIGHT: f32 = 0.1;
+
+    pub fn new(day_length: u64, steps: u64, time_of_day: f32) -> Sun {
+        Sun {
+            day_length,
+            steps,
+            ticks: (time_of_day * day_length as f32) as u64,
+        }
+    }
+
+    fn step(&self) -> u64 {
+        ((self.ticks % self.day_length) * self.steps) / self.day_length
+    }
+
+    pub fn tick(&mut self) -> bool {
+        let before = self.step();
+        self.ticks += 1;
+        self.step() != before
+    }
+
+    pub fn time_of_day(&self) -> f32 {
+        self.step() as f32 / self.steps as f32
+    }
+
+    fn elevation(&self) -> f32 {
+        (self.time_of_day() - 0.25) * 2.0 * PI
+    }
+
+    pub fn light_direction(&self) -> V3<f32> {
+        let elevation = self.elevation();
+        V3::new(
+            -elevation.cos(),
+            0.0,
+            elevation.sin().abs().max(Sun::MIN_LIGHT_HEIGHT),
+        )
+    }
+
+    pub fn tint(&self) -> Color {
+        let height = self.elevation().sin();
+        if height <= 0.0 {
+            Sun::NIGHT
+        } else if height < Sun::TWILIGHT_HEIGHT {
+            lerp(&Sun::NIGHT, &Sun::TWILIGHT, height / Sun::TWILIGHT_HEIGHT)
+        } else if height < Sun::TWILIGHT_HEIGHT * 2.0 {
+            let t = (height - Sun::TWILIGHT_HEIGHT) / Sun::TWILIGHT_HEIGHT;
+            lerp(&Sun::TWILIGHT, &Sun::DAY, t)
+        } else {
+            Sun::DAY
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn almost_equal(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    #[test]
+    fn test_tint() {
+        assert_eq!(
+            tint(
+                &Color::new(1.0, 0.5, 0.5, 1.0),
+                &Color::new(0.5, 0.5, 1.0, 1.0)
+            ),
+            Color::new(0.5, 0.25, 0.5, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_tick_changes_step() {
+        let mut sun = Sun::new(100, 4, 0.0);
+        for _ in 0..24 {
+            assert!(!sun.tick());
+        }
+        assert!(sun.tick());
+        assert!(almost_equal(sun.time_of_day(), 0.25));
+    }
+
+    #[test]
+    fn test_time_of_day_wraps() {
+        let mut sun = Sun::new(100, 4, 0.75);
+        for _ in 0..25 {
+            sun.tick();
+        }
+        assert!(almost_equal(sun.time_of_day(), 0.0));
+    }
+
+    #[test]
+    fn test_noon() {
+        let sun = Sun::new(100, 4, 0.5);
+        let light_direction = sun.light_direction();
+        assert!(almost_equal(light_direction.x, 0.0));
+        assert!(almost_equal(light_direction.z, 1.0));
+        assert_eq!(sun.tint(), Sun::DAY);
+    }
+
+    #[test]
+    fn test_sunrise() {
+        let sun = Sun::new(100, 4, 0.25);
+        let light_direction = sun.light_direction();
+        assert!(almost_equal(light_direction.x, -1.0));
+        assert!(almost_equal(light_direction.z, Sun::MIN_LIGHT_HEIGHT));
+        assert_eq!(sun.tint(), Sun::NIGHT);
+    }
+
+    #[test]
+    fn test_midnight() {
+        let sun = Sun::new(100, 4, 0.0);
+        assert_eq!(sun.tint(), Sun::NIGHT);
+    }
+
+    #[test]
+    fn test_twilight() {
+        let sun = Sun::new(4800, 48, 0.275);
+        let tint = sun.tint();
+        assert!(tint.r > Sun::NIGHT.r && tint.r <= Sun::TWILIGHT.r);
+        assert!(tint.b < Sun::NIGHT.b);
+    }
+}
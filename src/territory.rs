@@ -0,0 +1,226 @@
+use isometric::terrain::Edge;
+use isometric::*;
+
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
+pub enum Owner {
+    Player,
+    Settlement(usize),
+}
+
+pub struct Territory {
+    owners: M<Option<Owner>>,
+    claims: M<usize>,
+    settlements: usize,
+}
+
+impl Territory {
+    pub fn new(width: usize, height: usize) -> Territory {
+        Territory {
+            owners: M::from_element(width, height, None),
+            claims: M::from_element(width, height, 0),
+            settlements: 0,
+        }
+    }
+
+    fn in_bounds(&self, position: &V2<usize>) -> bool {
+        let (width, height) = self.owners.shape();
+        position.x < width && position.y < height
+    }
+
+    pub fn get(&self, position: &V2<usize>) -> Option<Owner> {
+        if self.in_bounds(position) {
+            self.owners[(position.x, position.y)]
+        } else {
+            None
+        }
+    }
+
+    pub fn new_settlement(&mut self) -> Owner {
+        self.settlements += 1;
+        Owner::Settlement(self.settlements - 1)
+    }
+
+    pub fn can_build(&self, position: &V2<usize>, owner: Owner) -> bool {
+        match self.get(position) {
+            Some(current) => current == owner,
+            None => self.in_bounds(position),
+        }
+    }
+
+    fn get_edge_tiles(&self, edge: &Edge) -> Vec<V2<usize>> {
+        let from = v2(
+            edge.from().x.min(edge.to().x),
+            edge.from().y.min(edge.to().y),
+        );
+        let mut out = vec![from];
+        if edge.horizontal() && from.y > 0 {
+            out.push(v2(from.x, from.y - 1));
+        } else if !edge.horizontal() && from.x > 0 {
+            out.push(v2(from.x - 1, from.y));
+        }
+        out.retain(|tile| self.in_bounds(tile));
+        out
+    }
+
+    pub fn can_build_road(&self, edge: &Edge, owner: Owner) -> bool {
+        self.get_edge_tiles(edge)
+            .iter()
+            .any(|tile| self.can_build(tile, owner))
+    }
+
+    pub fn claim(&mut self, position: &V2<usize>, radius: usize, owner: Owner) -> Vec<V2<usize>> {
+        let mut out = vec![];
+        for x in position.x.saturating_sub(radius)..=position.x + radius {
+            for y in position.y.saturating_sub(radius)..=position.y + radius {
+                let tile = v2(x, y);
+                if !self.in_bounds(&tile) {
+                    continue;
+                }
+                match self.get(&tile) {
+                    None => {
+                        self.owners[(x, y)] = Some(owner);
+                        self.claims[(x, y)] = 1;
+                        out.push(tile);
+                    }
+                    Some(current) if current == owner => self.claims[(x, y)] += 1,
+                    Some(_) => (),
+                }
+            }
+        }
+        out
+    }
+
+    pub fn release(&mut self, position: &V2<usize>, radius: usize, owner: Owner) -> Vec<V2<usize>> {
+        let mut out = vec![];
+        for x in position.x.saturating_sub(radius)..=position.x + radius {
+            for y in position.y.saturating_sub(radius)..=position.y + radius {
+                let tile = v2(x, y);
+                if self.in_bounds(&tile) && self.get(&tile) == Some(owner) {
+                    self.claims[(x, y)] -= 1;
+                    if self.claims[(x, y)] == 0 {
+                        self.owners[(x, y)] = None;
+                        out.push(tile);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    pub fn claim_road(&mut self, edge: &Edge, radius: usize, owner: Owner) -> Vec<V2<usize>> {
+        let mut out = vec![];
+        for tile in self.get_edge_tiles(edge) {
+            out.append(&mut self.claim(&tile, radius, owner));
+        }
+        out
+    }
+
+    pub fn release_road(&mut self, edge: &Edge, radius: usize, owner: Owner) -> Vec<V2<usize>> {
+        let mut out = vec![];
+        for tile in self.get_edge_tiles(edge) {
+            out.append(&mut self.release(&tile, radius, owner));
+        }
+        out
+    }
+
+    pub fn get_borders(&self, from: &V2<usize>, to: &V2<usize>) -> Vec<Edge> {
+        let mut out = vec![];
+        for x in from.x..to.x {
+            for y in from.y..to.y {
+                let tile = v2(x, y);
+                if !self.in_bounds(&tile) {
+                    continue;
+                }
+                let owner = self.get(&tile);
+                if x > 0 && owner != self.get(&v2(x - 1, y)) {
+                    out.push(Edge::new(tile, v2(x, y + 1)));
+                }
+                if y > 0 && owner != self.get(&v2(x, y - 1)) {
+                    out.push(Edge::new(tile, v2(x + 1, y)));
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_claim_skips_owned_tiles() {
+        let mut territory = Territory::new(5, 5);
+        let settlement = territory.new_settlement();
+        assert_eq!(territory.claim(&v2(0, 0), 1, settlement).len(), 4);
+        assert_eq!(territory.claim(&v2(1, 1), 1, Owner::Player).len(), 5);
+        assert_eq!(territory.get(&v2(0, 0)), Some(settlement));
+        assert_eq!(territory.get(&v2(2, 2)), Some(Owner::Player));
+    }
+
+    #[test]
+    fn test_release_keeps_tiles_claimed_by_others() {
+        let mut territory = Territory::new(5, 5);
+        territory.claim(&v2(0, 0), 1, Owner::Player);
+        territory.claim(&v2(2, 0), 1, Owner::Player);
+        let released = territory.release(&v2(0, 0), 1, Owner::Player);
+        assert_eq!(released.len(), 2);
+        assert_eq!(territory.get(&v2(0, 0)), None);
+        assert_eq!(territory.get(&v2(1, 0)), Some(Owner::Player));
+        assert_eq!(territory.release(&v2(2, 0), 1, Owner::Player).len(), 6);
+        assert_eq!(territory.get(&v2(1, 0)), None);
+    }
+
+    #[test]
+    fn test_can_build() {
+        let mut territory = Territory::new(5, 5);
+        let settlement = territory.new_settlement();
+        territory.claim(&v2(0, 0), 0, settlement);
+        territory.claim(&v2(1, 0), 0, Owner::Player);
+        assert!(!territory.can_build(&v2(0, 0), Owner::Player));
+        assert!(territory.can_build(&v2(1, 0), Owner::Player));
+        assert!(territory.can_build(&v2(2, 0), Owner::Player));
+        assert!(!territory.can_build(&v2(5, 0), Owner::Player));
+    }
+
+    #[test]
+    fn test_can_build_road() {
+        let mut territory = Territory::new(5, 5);
+        let settlement = territory.new_settlement();
+        territory.claim(&v2(1, 1), 1, settlement);
+        assert!(!territory.can_build_road(&Edge::new(v2(1, 1), v2(2, 1)), Owner::Player));
+        assert!(territory.can_build_road(&Edge::new(v2(1, 3), v2(2, 3)), Owner::Player));
+    }
+
+    #[test]
+    fn test_claim_road() {
+        let mut territory = Territory::new(5, 5);
+        territory.claim(&v2(0, 0), 0, Owner::Player);
+        let claimed = territory.claim_road(&Edge::new(v2(0, 0), v2(1, 0)), 1, Owner::Player);
+        assert_eq!(claimed.len(), 3);
+    }
+
+    #[test]
+    fn test_release_road() {
+        let mut territory = Territory::new(5, 5);
+        let edge = Edge::new(v2(1, 1), v2(2, 1));
+        territory.claim(&v2(0, 0), 0, Owner::Player);
+        territory.claim_road(&edge, 1, Owner::Player);
+        assert_eq!(territory.release_road(&edge, 1, Owner::Player).len(), 8);
+        assert_eq!(territory.get(&v2(0, 0)), Some(Owner::Player));
+        assert_eq!(territory.get(&v2(1, 1)), None);
+    }
+
+    #[test]
+    fn test_get_borders() {
+        let mut territory = Territory::new(3, 3);
+        territory.claim(&v2(1, 1), 0, Owner::Player);
+        let borders = territory.get_borders(&v2(0, 0), &v2(3, 3));
+        assert_eq!(borders.len(), 4);
+        assert!(borders.contains(&Edge::new(v2(1, 1), v2(1, 2))));
+        assert!(borders.contains(&Edge::new(v2(1, 1), v2(2, 1))));
+        assert!(borders.contains(&Edge::new(v2(2, 1), v2(2, 2))));
+        assert!(borders.contains(&Edge::new(v2(1, 2), v2(2, 2))));
+    }
+}
@@ -0,0 +1,99 @@
+use std::f32::consts::PI;
+
+pub struct Tide {
+    mean_level: f32,
+    amplitude: f32,
+    period: u64,
+    rise_per_tick: f32,
+    step: f32,
+    ticks: u64,
+}
+
+impl Tide {
+    pub fn new(
+        mean_level: f32,
+        amplitude: f32,
+        period: u64,
+        rise_per_tick: f32,
+        step: f32,
+    ) -> Tide {
+        assert!(period > 0, "Tide period must be positive");
+        assert!(step > 0.0, "Tide step must be positive");
+        Tide {
+            mean_level,
+            amplitude,
+            period,
+            rise_per_tick,
+            step,
+            ticks: 0,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    fn raw_level(&self) -> f32 {
+        let phase = ((self.ticks % self.period) as f32 / self.period as f32) * 2.0 * PI;
+        self.mean_level + self.rise_per_tick * self.ticks as f32 + self.amplitude * phase.sin()
+    }
+
+    pub fn level(&self) -> f32 {
+        (self.raw_level() / self.step).round() * self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn almost_equal(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    fn tick(tide: &mut Tide, ticks: u64) {
+        for _ in 0..ticks {
+            tide.tick();
+        }
+    }
+
+    #[test]
+    fn test_starts_at_mean_level() {
+        let tide = Tide::new(0.5, 0.1, 100, 0.0, 0.01);
+        assert!(almost_equal(tide.level(), 0.5));
+    }
+
+    #[test]
+    fn test_high_and_low_tide() {
+        let mut tide = Tide::new(0.5, 0.1, 100, 0.0, 0.01);
+        tick(&mut tide, 25);
+        assert!(almost_equal(tide.level(), 0.6));
+        tick(&mut tide, 50);
+        assert!(almost_equal(tide.level(), 0.4));
+        tick(&mut tide, 25);
+        assert!(almost_equal(tide.level(), 0.5));
+    }
+
+    #[test]
+    fn test_rise() {
+        let mut tide = Tide::new(0.5, 0.0, 100, 0.01, 0.01);
+        tick(&mut tide, 10);
+        assert!(almost_equal(tide.level(), 0.6));
+    }
+
+    #[test]
+    #[should_panic(expected = "Tide period must be positive")]
+    fn test_zero_period() {
+        Tide::new(0.5, 0.1, 0, 0.0, 0.01);
+    }
+
+    #[test]
+    fn test_level_is_quantized() {
+        let mut tide = Tide::new(0.5, 0.0, 100, 0.001, 0.1);
+        tick(&mut tide, 40);
+        assert!(almost_equal(tide.level(), 0.5));
+        tick(&mut tide, 20);
+        assert!(almost_equal(tide.level(), 0.6));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::roadset::RoadSet;
+use crate::territory::Owner;
+use isometric::terrain::Edge;
+use isometric::*;
+use std::collections::HashMap;
+
+pub struct Traffic {
+    counts: HashMap<Edge, f32>,
+    owners: HashMap<Edge, Owner>,
+    conditions: HashMap<Edge, f32>,
+    decay: f32,
+    wear: f32,
+    initial_condition: f32,
+    max_condition: f32,
+    upgrade_condition: f32,
+}
+
+impl Traffic {
+    pub fn new(wear: f32) -> Traffic {
+        Traffic {
+            counts: HashMap::new(),
+            owners: HashMap::new(),
+            conditions: HashMap::new(),
+            decay: 0.5,
+            wear,
+            initial_condition: 10.0,
+            max_condition: 20.0,
+            upgrade_condition: 10.0,
+        }
+    }
+
+    pub fn get_count(&self, edge: &Edge) -> f32 {
+        self.counts.get(edge).cloned().unwrap_or(0.0)
+    }
+
+    pub fn get_owner(&self, edge: &Edge) -> Option<Owner> {
+        self.owners.get(edge).cloned()
+    }
+
+    pub fn record(&mut self, edges: &[Edge], amount: f32, owner: Owner) {
+        for edge in edges {
+            *self.counts.entry(*edge).or_insert(0.0) += amount;
+            self.owners.insert(*edge, owner);
+        }
+    }
+
+    pub fn damage(&mut self, edge: &Edge, amount: f32) {
+        let condition = self.get_condition(edge, true) - amount;
+        self.conditions.insert(*edge, condition);
+    }
+
+    fn get_condition(&self, edge: &Edge, road: bool) -> f32 {
+        match self.conditions.get(edge) {
+            Some(condition) => *condition,
+            None if road => self.initial_condition,
+            None => 0.0,
+        }
+    }
+
+    pub fn wear(
+        &mut self,
+        roads: &RoadSet,
+        rivers: &RoadSet,
+        width: usize,
+        height: usize,
+    ) -> Vec<Edge> {
+        let mut edges = roads.get_edges(&v2(0, 0), &v2(width, height));
+        edges.extend(
+            self.counts
+                .keys()
+                .filter(|edge| !roads.is_road(edge))
+                .cloned(),
+        );
+        let mut out = vec![];
+        for edge in edges {
+            let road = roads.is_road(&edge);
+            let condition = self.get_condition(&edge, road) + self.get_count(&edge) - self.wear;
+            let condition = condition.min(self.max_condition);
+            if road && condition <= 0.0 {
+                self.conditions.remove(&edge);
+                out.push(edge);
+            } else if !road && condition <= 0.0 {
+                self.conditions.remove(&edge);
+            } else {
+                self.conditions.insert(edge, condition);
+                if !road && condition >= self.upgrade_condition && !rivers.is_road(&edge) {
+                    out.push(edge);
+                }
+            }
+        }
+        let decay = self.decay;
+        self.counts.values_mut().for_each(|count| *count *= decay);
+        self.counts.retain(|_, count| *count >= 0.01);
+        let counts = &self.counts;
+        self.owners.retain(|edge, _| counts.contains_key(edge));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn edge() -> Edge {
+        Edge::new(v2(0, 0), v2(1, 0))
+    }
+
+    #[test]
+    fn test_record_and_decay() {
+        let mut traffic = Traffic::new(1.0);
+        let roads = RoadSet::new(3, 3, 0.05);
+        traffic.record(&[edge(), edge()], 2.0, Owner::Player);
+        assert_eq!(traffic.get_count(&edge()), 4.0);
+        traffic.wear(&roads, &roads, 3, 3);
+        assert_eq!(traffic.get_count(&edge()), 2.0);
+    }
+
+    #[test]
+    fn test_unused_road_wears_away() {
+        let mut traffic = Traffic::new(1.0);
+        let mut roads = RoadSet::new(3, 3, 0.05);
+        let rivers = RoadSet::new(3, 3, 0.05);
+        roads.add_road(&edge());
+        for _ in 0..9 {
+            assert!(traffic.wear(&roads, &rivers, 3, 3).is_empty());
+        }
+        assert_eq!(traffic.wear(&roads, &rivers, 3, 3), vec![edge()]);
+    }
+
+    #[test]
+    fn test_used_road_is_maintained() {
+        let mut traffic = Traffic::new(1.0);
+        let mut roads = RoadSet::new(3, 3, 0.05);
+        let rivers = RoadSet::new(3, 3, 0.05);
+        roads.add_road(&edge());
+        for _ in 0..100 {
+            traffic.record(&[edge()], 1.0, Owner::Player);
+            assert!(traffic.wear(&roads, &rivers, 3, 3).is_empty());
+        }
+        assert_eq!(traffic.get_condition(&edge(), true), 20.0);
+    }
+
+    #[test]
+    fn test_damaged_road_is_cleared() {
+        let mut traffic = Traffic::new(1.0);
+        let mut roads = RoadSet::new(3, 3, 0.05);
+        let rivers = RoadSet::new(3, 3, 0.05);
+        roads.add_road(&edge());
+        traffic.damage(&edge(), 9.0);
+        assert_eq!(traffic.wear(&roads, &rivers, 3, 3), vec![edge()]);
+    }
+
+    #[test]
+    fn test_busy_track_becomes_road() {
+        let mut traffic = Traffic::new(1.0);
+        let roads = RoadSet::new(3, 3, 0.05);
+        let rivers = RoadSet::new(3, 3, 0.05);
+        traffic.record(&[edge()], 6.0, Owner::Player);
+        assert!(traffic.wear(&roads, &rivers, 3, 3).is_empty());
+        traffic.record(&[edge()], 6.0, Owner::Player);
+        assert_eq!(traffic.wear(&roads, &rivers, 3, 3), vec![edge()]);
+    }
+
+    #[test]
+    fn test_river_is_not_upgraded() {
+        let mut traffic = Traffic::new(1.0);
+        let roads = RoadSet::new(3, 3, 0.05);
+        let mut rivers = RoadSet::new(3, 3, 0.05);
+        rivers.add_road(&edge());
+        traffic.record(&[edge()], 20.0, Owner::Player);
+        assert!(traffic.wear(&roads, &rivers, 3, 3).is_empty());
+    }
+}
@@ -0,0 +1,519 @@
+use crate::roadset::RoadSet;
+use crate::utils::float_ordering;
+use crate::world::World;
+use isometric::coords::WorldCoord;
+use isometric::drawing::Text;
+use isometric::terrain::Edge;
+use isometric::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TravelCosts {
+    pub ticks_per_tile: f32,
+    pub max_grade: f32,
+    pub uphill: f32,
+    pub downhill: f32,
+    pub road: f32,
+    pub sea: f32,
+}
+
+fn is_node(roads: &RoadSet, position: &V2<usize>) -> bool {
+    let node = roads.get_node(*position);
+    node.width() > 0.0 || node.height() > 0.0
+}
+
+fn get_river_width(world: &World, from: &V2<usize>, to: &V2<usize>) -> Option<f32> {
+    if from.x == to.x || from.y == to.y {
+        world.get_river_width(&Edge::new(*from, *to))
+    } else {
+        None
+    }
+}
+
+pub fn is_sailing(world: &World, from: &V2<usize>, to: &V2<usize>) -> bool {
+    world.is_sea(to) || get_river_width(world, from, to).is_some()
+}
+
+impl TravelCosts {
+    pub fn new(ticks_per_tile: f32, max_grade: f32) -> TravelCosts {
+        TravelCosts {
+            ticks_per_tile,
+            max_grade,
+            uphill: 2.0,
+            downhill: 0.5,
+            road: 0.5,
+            sea: 0.5,
+        }
+    }
+
+    pub fn scale(&self, factor: f32) -> TravelCosts {
+        TravelCosts {
+            ticks_per_tile: self.ticks_per_tile * factor,
+            ..*self
+        }
+    }
+
+    pub fn walk(&self, distance: f32, rise: f32, road: bool) -> Option<f32> {
+        if rise.abs() / distance >= self.max_grade {
+            return None;
+        }
+        let slope = if rise > 0.0 {
+            1.0 + self.uphill * rise / distance
+        } else {
+            1.0 + self.downhill * -rise / distance
+        };
+        let road = if road { self.road } else { 1.0 };
+        Some(self.ticks_per_tile * distance * slope * road)
+    }
+
+    pub fn sail(&self, distance: f32, river_width: Option<f32>, rise: f32) -> Option<f32> {
+        let factor = match river_width {
+            Some(_) if rise.abs() / distance >= self.max_grade => return None,
+            Some(width) => {
+                let current = if rise < 0.0 {
+                    0.5
+                } else if rise > 0.0 {
+                    2.0
+                } else {
+                    1.0
+                };
+                current / (1.0 + width * 4.0)
+            }
+            None => self.sea,
+        };
+        Some(self.ticks_per_tile * distance * factor)
+    }
+
+    pub fn get_cost(&self, world: &World, from: &V2<usize>, to: &V2<usize>) -> Option<f32> {
+        let (from_z, to_z) = match (world.get_elevation(from), world.get_elevation(to)) {
+            (Some(from_z), Some(to_z)) => (from_z, to_z),
+            _ => return None,
+        };
+        let dx = to.x as f32 - from.x as f32;
+        let dy = to.y as f32 - from.y as f32;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let sea_level = world.sea_level();
+        let rise = to_z.max(sea_level) - from_z.max(sea_level);
+        if is_sailing(world, from, to) {
+            return self.sail(distance, get_river_width(world, from, to), rise);
+        }
+        if is_node(world.rivers(), to) && !is_node(world.roads(), to) {
+            return None;
+        }
+        let road = world
+            .get_road_edges(from, to)
+            .iter()
+            .all(|edge| world.roads().is_road(edge));
+        self.walk(distance, rise, road)
+    }
+}
+
+#[derive(PartialEq)]
+struct Visit {
+    position: V2<usize>,
+    cost: f32,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Visit) -> Ordering {
+        float_ordering(&other.cost, &self.cost)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Visit) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn get_neighbours(world: &World, position: &V2<usize>) -> Vec<V2<usize>> {
+    let mut out = vec![];
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let x = position.x as i64 + dx;
+            let y = position.y as i64 + dy;
+            if (dx, dy) != (0, 0) && x >= 0 && y >= 0 {
+                let neighbour = v2(x as usize, y as usize);
+                if world.in_bounds(&neighbour) {
+                    out.push(neighbour);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn get_road_neighbours(world: &World, position: &V2<usize>) -> Vec<V2<usize>> {
+    get_neighbours(world, position)
+        .into_iter()
+        .filter(|neighbour| {
+            (neighbour.x == position.x || neighbour.y == position.y)
+                && world.roads().is_road(&Edge::new(*position, *neighbour))
+        })
+        .collect()
+}
+
+fn get_path(previous: &M<Option<V2<usize>>>, to: V2<usize>) -> Vec<V2<usize>> {
+    let mut out = vec![to];
+    while let Some(position) = previous[(out[out.len() - 1].x, out[out.len() - 1].y)] {
+        out.push(position);
+    }
+    out.reverse();
+    out
+}
+
+pub struct RouteTree {
+    best: M<f32>,
+    previous: M<Option<V2<usize>>>,
+}
+
+impl RouteTree {
+    pub fn get_cost(&self, to: &V2<usize>) -> Option<f32> {
+        Some(self.best[(to.x, to.y)]).filter(|cost| cost.is_finite())
+    }
+
+    pub fn get_route(&self, to: &[V2<usize>]) -> Option<(f32, Vec<V2<usize>>)> {
+        let mut out: Option<(f32, V2<usize>)> = None;
+        for position in to {
+            if let Some(cost) = self.get_cost(position) {
+                if out.map_or(true, |(best, _)| cost < best) {
+                    out = Some((cost, *position));
+                }
+            }
+        }
+        out.map(|(cost, position)| (cost, get_path(&self.previous, position)))
+    }
+}
+
+pub fn get_route_tree(
+    world: &World,
+    costs: &TravelCosts,
+    from: &[V2<usize>],
+    max_cost: f32,
+    roads_only: bool,
+) -> RouteTree {
+    let mut best = M::from_element(world.width(), world.height(), f32::INFINITY);
+    let mut previous = M::from_element(world.width(), world.height(), None);
+    let mut queue = BinaryHeap::new();
+    for position in from {
+        best[(position.x, position.y)] = 0.0;
+        queue.push(Visit {
+            position: *position,
+            cost: 0.0,
+        });
+    }
+    while let Some(Visit { position, cost }) = queue.pop() {
+        if cost > best[(position.x, position.y)] {
+            continue;
+        }
+        let neighbours = if roads_only {
+            get_road_neighbours(world, &position)
+        } else {
+            get_neighbours(world, &position)
+        };
+        for neighbour in neighbours {
+            if let Some(step) = costs.get_cost(world, &position, &neighbour) {
+                let cost = cost + step;
+                let index = (neighbour.x, neighbour.y);
+                if cost <= max_cost && cost < best[index] {
+                    best[index] = cost;
+                    previous[index] = Some(position);
+                    queue.push(Visit {
+                        position: neighbour,
+                        cost,
+                    });
+                }
+            }
+        }
+    }
+    RouteTree { best, previous }
+}
+
+pub struct TravelReadout {
+    font: Arc<Font>,
+    ticks_per_hour: f32,
+    max_cost: f32,
+    route: Option<(V2<usize>, V2<usize>, u64)>,
+    tree: Option<(V2<usize>, TravelCosts, u64, RouteTree)>,
+}
+
+impl TravelReadout {
+    pub fn new(ticks_per_hour: f32, max_cost: f32) -> TravelReadout {
+        TravelReadout {
+            font: Arc::new(Font::from_csv_and_texture(
+                "serif.csv",
+                Texture::new(image::open("serif.png").unwrap()),
+            )),
+            ticks_per_hour,
+            max_cost,
+            route: None,
+            tree: None,
+        }
+    }
+
+    fn get_text(&self, travel_time: Option<f32>) -> String {
+        match travel_time {
+            Some(ticks) => format!("{:.1}h", ticks / self.ticks_per_hour),
+            None => "No route".to_string(),
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        world: &World,
+        costs: &TravelCosts,
+        from: Option<WorldCoord>,
+        to: Option<WorldCoord>,
+    ) -> Vec<Command> {
+        let (from, to) = match (from, to) {
+            (Some(from), Some(to)) => (from, to),
+            _ => {
+                self.route = None;
+                return vec![Command::Erase("travel-estimate".to_string())];
+            }
+        };
+        let route = (
+            v2(from.x.round() as usize, from.y.round() as usize),
+            v2(to.x.round() as usize, to.y.round() as usize),
+            world.version(),
+        );
+        if self.route == Some(route) || !world.in_bounds(&route.1) {
+            return vec![];
+        }
+        self.route = Some(route);
+        let stale = match self.tree {
+            Some((origin, ref tree_costs, version, _)) => {
+                origin != route.0 || tree_costs != costs || version != route.2
+            }
+            None => true,
+        };
+        if stale {
+            let tree = get_route_tree(world, costs, &[route.0], self.max_cost, false);
+            self.tree = Some((route.0, *costs, route.2, tree));
+        }
+        let travel_time = self
+            .tree
+            .as_ref()
+            .and_then(|(_, _, _, tree)| tree.get_cost(&route.1));
+        vec![Command::Draw {
+            name: "travel-estimate".to_string(),
+            drawing: Box::new(Text::new(
+                &self.get_text(travel_time),
+                to,
+                self.font.clone(),
+            )),
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lakes::get_lakes;
+    use isometric::terrain::Node;
+
+    fn get_travel_time(
+        world: &World,
+        costs: &TravelCosts,
+        from: &V2<usize>,
+        to: &V2<usize>,
+        max_cost: f32,
+    ) -> Option<f32> {
+        get_route_tree(world, costs, &[*from], max_cost, false).get_cost(to)
+    }
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        let mut world = World::new(
+            M::from_vec(4, 4, vec![
+                1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0,
+            ]),
+            vec![
+                Node::new(v2(0, 2), 0.0, 0.2),
+                Node::new(v2(1, 2), 0.0, 0.2),
+                Node::new(v2(2, 2), 0.0, 0.2),
+                Node::new(v2(3, 2), 0.0, 0.2),
+            ],
+            vec![
+                Edge::new(v2(0, 2), v2(1, 2)),
+                Edge::new(v2(1, 2), v2(2, 2)),
+                Edge::new(v2(2, 2), v2(3, 2)),
+            ],
+            vec![],
+            0.5
+        );
+        world.add_road(&Edge::new(v2(0, 0), v2(1, 0)));
+        world
+    }
+
+    #[test]
+    fn test_travel_time_straight() {
+        let costs = TravelCosts::new(10.0, 1.0);
+        assert_eq!(
+            get_travel_time(&world(), &costs, &v2(1, 0), &v2(3, 0), 100.0),
+            Some(20.0)
+        );
+    }
+
+    #[test]
+    fn test_scaled_travel_time() {
+        let costs = TravelCosts::new(10.0, 1.0).scale(1.5);
+        assert_eq!(
+            get_travel_time(&world(), &costs, &v2(1, 0), &v2(3, 0), 100.0),
+            Some(30.0)
+        );
+    }
+
+    #[test]
+    fn test_travel_time_road() {
+        let costs = TravelCosts::new(10.0, 1.0);
+        assert_eq!(
+            get_travel_time(&world(), &costs, &v2(0, 0), &v2(1, 0), 100.0),
+            Some(5.0)
+        );
+    }
+
+    #[test]
+    fn test_river_without_bridge_is_impassable() {
+        let costs = TravelCosts::new(10.0, 1.0);
+        assert_eq!(
+            get_travel_time(&world(), &costs, &v2(0, 0), &v2(0, 3), 1000.0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_river_with_bridge() {
+        let costs = TravelCosts::new(10.0, 1.0);
+        let mut world = world();
+        world.add_road(&Edge::new(v2(1, 1), v2(1, 2)));
+        world.add_road(&Edge::new(v2(1, 2), v2(1, 3)));
+        assert_eq!(
+            get_travel_time(&world, &costs, &v2(1, 1), &v2(1, 3), 1000.0),
+            Some(10.0)
+        );
+    }
+
+    #[test]
+    fn test_max_cost() {
+        let costs = TravelCosts::new(10.0, 1.0);
+        assert_eq!(
+            get_travel_time(&world(), &costs, &v2(1, 0), &v2(3, 0), 15.0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_route_on_roads() {
+        let costs = TravelCosts::new(10.0, 1.0);
+        let mut world = world();
+        world.add_road(&Edge::new(v2(1, 0), v2(2, 0)));
+        assert_eq!(
+            get_route_tree(&world, &costs, &[v2(0, 0)], 100.0, true).get_route(&[v2(2, 0)]),
+            Some((10.0, vec![v2(0, 0), v2(1, 0), v2(2, 0)]))
+        );
+        assert_eq!(
+            get_route_tree(&world, &costs, &[v2(0, 0)], 100.0, true).get_route(&[v2(3, 0)]),
+            None
+        );
+    }
+
+    #[rustfmt::skip]
+    fn world_with_river() -> World {
+        World::new(
+            M::from_vec(3, 3, vec![
+                1.0, 1.0, 1.0,
+                1.0, 2.0, 1.0,
+                1.0, 1.0, 1.0,
+            ]),
+            vec![
+                Node::new(v2(1, 0), 0.1, 0.0),
+                Node::new(v2(1, 1), 0.2, 0.0),
+            ],
+            vec![
+                Edge::new(v2(1, 0), v2(1, 1)),
+            ],
+            vec![],
+            0.5
+        )
+    }
+
+    #[rustfmt::skip]
+    fn world_with_lake() -> World {
+        let elevations = M::from_vec(3, 3, vec![
+            2.0, 2.0, 2.0,
+            2.0, 1.0, 2.0,
+            2.0, 2.0, 2.0,
+        ]);
+        let lakes = get_lakes(&elevations, 0.5);
+        World::new(elevations, vec![], vec![], lakes, 0.5)
+    }
+
+    #[test]
+    fn test_get_cost() {
+        let costs = TravelCosts::new(10.0, 2.0);
+        let world = world_with_river();
+        assert_eq!(costs.get_cost(&world, &v2(0, 0), &v2(0, 1)), Some(10.0));
+        assert_eq!(costs.get_cost(&world, &v2(0, 1), &v2(0, 2)), Some(10.0));
+        assert_eq!(costs.get_cost(&world, &v2(0, 0), &v2(1, 1)), None);
+        assert_eq!(costs.get_cost(&world, &v2(0, 0), &v2(1, 0)), None);
+        let upstream = costs.sail(1.0, Some(0.2), 1.0);
+        assert!(upstream.is_some());
+        assert_eq!(costs.get_cost(&world, &v2(1, 0), &v2(1, 1)), upstream);
+        let costs = TravelCosts::new(10.0, 0.5);
+        assert_eq!(costs.get_cost(&world, &v2(1, 0), &v2(1, 1)), None);
+    }
+
+    #[test]
+    fn test_get_cost_slopes() {
+        let costs = TravelCosts::new(10.0, 2.0);
+        let world = world_with_lake();
+        assert_eq!(costs.get_cost(&world, &v2(1, 1), &v2(1, 0)), Some(30.0));
+        assert_eq!(costs.get_cost(&world, &v2(1, 0), &v2(1, 1)), Some(15.0));
+    }
+
+    #[test]
+    fn test_get_cost_sea() {
+        let costs = TravelCosts::new(10.0, 2.0);
+        let mut world = world_with_lake();
+        world.set_sea_level(1.5);
+        assert_eq!(costs.get_cost(&world, &v2(1, 0), &v2(1, 1)), Some(5.0));
+    }
+
+    #[test]
+    fn test_get_cost_disembark_from_deep_sea() {
+        let costs = TravelCosts::new(10.0, 0.75);
+        let mut world = world_with_lake();
+        world.set_sea_level(1.5);
+        assert_eq!(costs.get_cost(&world, &v2(1, 1), &v2(1, 0)), Some(20.0));
+    }
+
+    #[test]
+    fn test_walk() {
+        let costs = TravelCosts::new(10.0, 0.5);
+        assert_eq!(costs.walk(1.0, 0.0, false), Some(10.0));
+        assert_eq!(costs.walk(1.0, 0.25, false), Some(15.0));
+        assert_eq!(costs.walk(1.0, -0.25, false), Some(11.25));
+        assert_eq!(costs.walk(1.0, 0.0, true), Some(5.0));
+        assert_eq!(costs.walk(1.0, 0.5, false), None);
+    }
+
+    #[test]
+    fn test_sail() {
+        let costs = TravelCosts::new(10.0, 0.5);
+        assert_eq!(costs.sail(1.0, None, 0.0), Some(5.0));
+        assert_eq!(costs.sail(1.0, Some(0.25), 0.0), Some(5.0));
+        assert_eq!(costs.sail(1.0, Some(0.25), -0.1), Some(2.5));
+        assert_eq!(costs.sail(1.0, Some(0.25), 0.1), Some(10.0));
+        assert_eq!(costs.sail(1.0, Some(0.25), 0.5), None);
+        assert_eq!(costs.sail(1.0, None, 0.5), Some(5.0));
+    }
+}
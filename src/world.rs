@@ -1,17 +1,37 @@
+use crate::climate::Season;
+use crate::lakes::{get_lakes, Lake};
+use crate::resources::Deposit;
 use crate::roadset::*;
+use crate::territory::{Owner, Territory};
+use crate::traffic::Traffic;
 use crate::utils::float_ordering;
 use isometric::coords::WorldCoord;
 use isometric::terrain::*;
 use isometric::*;
+use std::collections::{HashMap, HashSet};
 
 pub struct World {
     width: usize,
     height: usize,
     terrain: Terrain,
     rivers: RoadSet,
+    river_nodes: Vec<Node>,
+    river_swell: f32,
     roads: RoadSet,
+    road_owners: HashMap<Edge, Owner>,
+    lakes: Vec<Lake>,
+    lake_map: M<Option<usize>>,
+    explored: M<bool>,
+    resources: M<Option<Deposit>>,
+    farmland: M<bool>,
+    snow: M<f32>,
+    flooded: M<bool>,
+    season: Season,
+    traffic: Traffic,
+    territory: Territory,
     sea_level: f32,
     max_height: f32,
+    version: u64,
 }
 
 impl World {
@@ -21,11 +41,13 @@ impl World {
         elevations: M<f32>,
         river_nodes: Vec<Node>,
         rivers: Vec<Edge>,
+        lakes: Vec<Lake>,
         sea_level: f32,
     ) -> World {
         let (width, height) = elevations.shape();
         let max_height = elevations.max();
-        let rivers = World::setup_rivers(width, height, river_nodes, rivers);
+        let rivers = World::setup_rivers(width, height, river_nodes.clone(), rivers);
+        let lake_map = World::setup_lake_map(width, height, &lakes);
         let from = &v2(0, 0);
         let to = &v2(width, height);
         World {
@@ -37,9 +59,23 @@ impl World {
                 &rivers.get_edges(from, to),
             ),
             rivers,
+            river_nodes,
+            river_swell: 1.0,
             roads: RoadSet::new(width, height, World::ROAD_WIDTH),
+            road_owners: HashMap::new(),
+            lakes,
+            lake_map,
+            explored: M::from_element(width, height, false),
+            resources: M::from_element(width - 1, height - 1, None),
+            farmland: M::from_element(width - 1, height - 1, false),
+            snow: M::from_element(width - 1, height - 1, 0.0),
+            flooded: M::from_element(width - 1, height - 1, false),
+            season: Season::Spring,
+            traffic: Traffic::new(1.0),
+            territory: Territory::new(width - 1, height - 1),
             sea_level,
             max_height,
+            version: 0,
         }
     }
 
@@ -63,10 +99,98 @@ impl World {
         &self.roads
     }
 
+    pub fn lakes(&self) -> &Vec<Lake> {
+        &self.lakes
+    }
+
+    pub fn explored(&self) -> &M<bool> {
+        &self.explored
+    }
+
+    pub fn set_explored(&mut self, explored: M<bool>) {
+        self.explored = explored;
+    }
+
+    pub fn set_resources(&mut self, resources: M<Option<Deposit>>) {
+        self.resources = resources;
+    }
+
+    pub fn is_farmland(&self, position: &V2<usize>) -> bool {
+        let (width, height) = self.farmland.shape();
+        position.x < width && position.y < height && self.farmland[(position.x, position.y)]
+    }
+
+    pub fn set_farmland(&mut self, position: &V2<usize>, farmland: bool) {
+        self.farmland[(position.x, position.y)] = farmland;
+    }
+
+    pub fn get_deposit(&self, position: &V2<usize>) -> Option<Deposit> {
+        let (width, height) = self.resources.shape();
+        if position.x < width && position.y < height {
+            self.resources[(position.x, position.y)]
+        } else {
+            None
+        }
+    }
+
+    pub fn harvest(&mut self, position: &V2<usize>, amount: f32) -> f32 {
+        let index = (position.x, position.y);
+        match self.get_deposit(position) {
+            Some(mut deposit) => {
+                let harvested = deposit.amount.min(amount);
+                deposit.amount -= harvested;
+                self.resources[index] = if deposit.amount > 0.0 {
+                    Some(deposit)
+                } else {
+                    None
+                };
+                harvested
+            }
+            None => 0.0,
+        }
+    }
+
     pub fn sea_level(&self) -> f32 {
         self.sea_level
     }
 
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn set_sea_level(&mut self, sea_level: f32) {
+        self.version += 1;
+        self.sea_level = sea_level;
+    }
+
+    pub fn season(&self) -> Season {
+        self.season
+    }
+
+    pub fn set_season(&mut self, season: Season) {
+        self.season = season;
+    }
+
+    pub fn get_snow(&self, position: &V2<usize>) -> f32 {
+        let (width, height) = self.snow.shape();
+        if position.x < width && position.y < height {
+            self.snow[(position.x, position.y)]
+        } else {
+            0.0
+        }
+    }
+
+    pub fn set_snow(&mut self, position: &V2<usize>, snow: f32) {
+        self.snow[(position.x, position.y)] = snow;
+    }
+
+    pub fn is_sea(&self, position: &V2<usize>) -> bool {
+        match self.get_elevation(position) {
+            Some(elevation) => elevation < self.sea_level,
+            None => false,
+        }
+    }
+
     pub fn max_height(&self) -> f32 {
         self.max_height
     }
@@ -83,6 +207,16 @@ impl World {
         out
     }
 
+    fn setup_lake_map(width: usize, height: usize, lakes: &[Lake]) -> M<Option<usize>> {
+        let mut out = M::from_element(width, height, None);
+        for (i, lake) in lakes.iter().enumerate() {
+            for position in lake.positions() {
+                out[(position.x, position.y)] = Some(i);
+            }
+        }
+        out
+    }
+
     fn get_horizontal_width(&self, position: &V2<usize>) -> f32 {
         self.rivers
             .get_horizontal_width(position)
@@ -115,16 +249,200 @@ impl World {
         self.update_terrain(edge);
     }
 
-    pub fn toggle_road(&mut self, edge: &Edge) {
+    pub fn claim_road(&mut self, edge: &Edge, owner: Owner) -> Vec<V2<usize>> {
+        self.road_owners.insert(*edge, owner);
+        self.territory.claim_road(edge, 1, owner)
+    }
+
+    pub fn release_road(&mut self, edge: &Edge) -> Vec<V2<usize>> {
+        match self.road_owners.remove(edge) {
+            Some(owner) => self.territory.release_road(edge, 1, owner),
+            None => vec![],
+        }
+    }
+
+    pub fn toggle_road(&mut self, edge: &Edge, owner: Owner) -> Option<Vec<V2<usize>>> {
         if self.roads.is_road(edge) {
             self.clear_road(edge);
-        } else {
+            Some(self.release_road(edge))
+        } else if self.can_build_road(edge, owner) {
             self.add_road(edge);
+            Some(self.claim_road(edge, owner))
+        } else {
+            None
         }
-        self.update_terrain(edge);
+    }
+
+    pub fn can_build_road(&self, edge: &Edge, owner: Owner) -> bool {
+        let on_water = [edge.from(), edge.to()]
+            .iter()
+            .any(|corner| self.is_sea(corner) || self.get_lake_level(corner).is_some());
+        !on_water && self.territory.can_build_road(edge, owner)
+    }
+
+    pub fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+
+    pub fn record_traffic(&mut self, from: &V2<usize>, to: &V2<usize>, amount: f32, owner: Owner) {
+        let edges = self.get_road_edges(from, to);
+        self.traffic.record(&edges, amount, owner);
+    }
+
+    pub fn wear_roads(&mut self) -> Vec<V2<usize>> {
+        let edges = self
+            .traffic
+            .wear(&self.roads, &self.rivers, self.width, self.height);
+        let mut out = vec![];
+        for edge in edges {
+            let owner = self.traffic.get_owner(&edge).unwrap_or(Owner::Player);
+            if let Some(mut changed) = self.toggle_road(&edge, owner) {
+                out.push(*edge.from());
+                out.push(*edge.to());
+                out.append(&mut changed);
+            }
+        }
+        out
+    }
+
+    pub fn territory(&self) -> &Territory {
+        &self.territory
+    }
+
+    pub fn territory_mut(&mut self) -> &mut Territory {
+        &mut self.territory
+    }
+
+    pub fn is_flooded(&self, position: &V2<usize>) -> bool {
+        let (width, height) = self.flooded.shape();
+        position.x < width && position.y < height && self.flooded[(position.x, position.y)]
+    }
+
+    pub fn set_flooded(&mut self, position: &V2<usize>, flooded: bool) {
+        self.version += 1;
+        self.flooded[(position.x, position.y)] = flooded;
+    }
+
+    pub fn has_road(&self, position: &V2<usize>) -> bool {
+        let [a, b, c, d] = self.get_corners(position);
+        [(a, b), (a, d), (b, c), (d, c)]
+            .iter()
+            .any(|(from, to)| self.roads.is_road(&Edge::new(*from, *to)))
+    }
+
+    pub fn damage_roads(&mut self, position: &V2<usize>, amount: f32) {
+        let [a, b, c, d] = self.get_corners(position);
+        for (from, to) in [(a, b), (a, d), (b, c), (d, c)].iter() {
+            let edge = Edge::new(*from, *to);
+            if self.roads.is_road(&edge) {
+                self.traffic.damage(&edge, amount);
+            }
+        }
+    }
+
+    pub fn river_nodes(&self) -> &Vec<Node> {
+        &self.river_nodes
+    }
+
+    fn update_river_widths(&mut self) -> Vec<V2<usize>> {
+        let swell = self.river_swell;
+        let nodes: Vec<Node> = self
+            .river_nodes
+            .iter()
+            .map(|node| Node::new(node.position(), node.width() * swell, node.height() * swell))
+            .collect();
+        self.version += 1;
+        self.rivers.set_widths_from_nodes(&nodes);
+        let mut out = vec![];
+        for node in nodes {
+            let position = node.position();
+            self.terrain.set_node(self.get_node(&position));
+            out.push(position);
+        }
+        out
+    }
+
+    pub fn set_river_swell(&mut self, swell: f32) -> Vec<V2<usize>> {
+        if swell == self.river_swell {
+            return vec![];
+        }
+        self.river_swell = swell;
+        self.update_river_widths()
+    }
+
+    pub fn widen_rivers(&mut self, factor: f32, max_width: f32) -> Vec<V2<usize>> {
+        for node in self.river_nodes.iter_mut() {
+            *node = Node::new(
+                node.position(),
+                (node.width() * factor).min(max_width),
+                (node.height() * factor).min(max_width),
+            );
+        }
+        self.update_river_widths()
+    }
+
+    pub fn set_elevations(&mut self, elevations: M<f32>) -> Vec<V2<usize>> {
+        self.version += 1;
+        self.max_height = elevations.max();
+        let mut out = vec![];
+        let lakes = get_lakes(&elevations, self.sea_level);
+        if lakes != self.lakes {
+            for lake in self.lakes.iter().chain(lakes.iter()) {
+                out.extend(lake.positions().iter().cloned());
+            }
+            self.lake_map = World::setup_lake_map(self.width, self.height, &lakes);
+            self.lakes = lakes;
+        }
+        let from = &v2(0, 0);
+        let to = &v2(self.width, self.height);
+        let mut nodes = vec![];
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let node = self.get_node(&v2(x, y));
+                if node.width() > 0.0 || node.height() > 0.0 {
+                    nodes.push(node);
+                }
+            }
+        }
+        let mut edges = self.rivers.get_edges(from, to);
+        edges.append(&mut self.roads.get_edges(from, to));
+        self.terrain = Terrain::new(elevations, &nodes, &edges);
+        out
+    }
+
+    pub fn get_river_width(&self, edge: &Edge) -> Option<f32> {
+        if !self.rivers.is_road(edge) {
+            return None;
+        }
+        let width = |position: &V2<usize>| {
+            if edge.horizontal() {
+                self.rivers.get_horizontal_width(position)
+            } else {
+                self.rivers.get_vertical_width(position)
+            }
+        };
+        Some(width(edge.from()).max(width(edge.to())))
+    }
+
+    pub fn get_road_edges(&self, from: &V2<usize>, to: &V2<usize>) -> Vec<Edge> {
+        if from.x == to.x || from.y == to.y {
+            return vec![Edge::new(*from, *to)];
+        }
+        let max_rise = |corner: &V2<usize>| {
+            let a = self.get_rise(&Edge::new(*from, *corner)).unwrap().abs();
+            let b = self.get_rise(&Edge::new(*corner, *to)).unwrap().abs();
+            a.max(b)
+        };
+        let corners = [v2(to.x, from.y), v2(from.x, to.y)];
+        let corner = corners
+            .iter()
+            .min_by(|a, b| float_ordering(&max_rise(a), &max_rise(b)))
+            .unwrap();
+        vec![Edge::new(*from, *corner), Edge::new(*corner, *to)]
     }
 
     fn update_terrain(&mut self, edge: &Edge) {
+        self.version += 1;
         if self.is_river_or_road(edge) {
             self.terrain.set_edge(edge);
         } else {
@@ -181,6 +499,132 @@ impl World {
         }
     }
 
+    pub fn get_lake_level(&self, position: &V2<usize>) -> Option<f32> {
+        if self.in_bounds(position) {
+            self.lake_map[(position.x, position.y)].map(|lake| self.lakes[lake].level())
+        } else {
+            None
+        }
+    }
+
+    pub fn is_lake(&self, position: &V2<usize>) -> bool {
+        self.get_corners(position)
+            .iter()
+            .any(|corner| self.get_lake_level(corner).is_some())
+    }
+
+    pub fn is_explored(&self, position: &V2<usize>) -> bool {
+        self.in_bounds(position) && self.explored[(position.x, position.y)]
+    }
+
+    pub fn is_tile_explored(&self, position: &V2<usize>) -> bool {
+        self.get_corners(position)
+            .iter()
+            .any(|corner| self.is_explored(corner))
+    }
+
+    pub fn explore(&mut self, position: &V2<usize>) -> bool {
+        if self.is_explored(position) || !self.in_bounds(position) {
+            false
+        } else {
+            self.explored[(position.x, position.y)] = true;
+            true
+        }
+    }
+
+    pub fn viewshed(
+        &self,
+        observer: &WorldCoord,
+        eye_height: f32,
+        radius: usize,
+    ) -> HashSet<V2<usize>> {
+        let mut out = HashSet::new();
+        let origin = v2(observer.x.round() as i64, observer.y.round() as i64);
+        if origin.x >= 0 && origin.y >= 0 {
+            let origin = v2(origin.x as usize, origin.y as usize);
+            if self.in_bounds(&origin) {
+                out.insert(origin);
+            }
+        }
+        let radius = radius as i64;
+        for offset in -radius..=radius {
+            let targets = [
+                v2(origin.x + offset, origin.y - radius),
+                v2(origin.x + offset, origin.y + radius),
+                v2(origin.x - radius, origin.y + offset),
+                v2(origin.x + radius, origin.y + offset),
+            ];
+            for target in targets.iter() {
+                self.cast_sight_line(observer, eye_height, target, radius as f32, &mut out);
+            }
+        }
+        out
+    }
+
+    fn cast_sight_line(
+        &self,
+        observer: &WorldCoord,
+        eye_height: f32,
+        target: &V2<i64>,
+        radius: f32,
+        out: &mut HashSet<V2<usize>>,
+    ) {
+        let eye_z = observer.z + eye_height;
+        let dx = target.x as f32 - observer.x;
+        let dy = target.y as f32 - observer.y;
+        let steps = dx.abs().max(dy.abs()).ceil() as usize;
+        let mut max_slope = f32::NEG_INFINITY;
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let (px, py) = (observer.x + dx * t, observer.y + dy * t);
+            let x = px.round();
+            let y = py.round();
+            if x < 0.0 || y < 0.0 {
+                return;
+            }
+            let position = v2(x as usize, y as usize);
+            if !self.in_bounds(&position) {
+                return;
+            }
+            let distance = ((x - observer.x).powf(2.0) + (y - observer.y).powf(2.0)).sqrt();
+            if distance > radius {
+                return;
+            }
+            if distance == 0.0 {
+                continue;
+            }
+            let elevation = self.terrain.elevations()[(position.x, position.y)];
+            let slope = (elevation - eye_z) / distance;
+            if slope >= max_slope {
+                out.insert(position);
+            }
+            let surface = self.get_surface_elevation(px, py).unwrap_or(elevation);
+            let surface_slope =
+                (surface - eye_z) / ((dx * t).powf(2.0) + (dy * t).powf(2.0)).sqrt();
+            max_slope = max_slope.max(slope).max(surface_slope);
+        }
+    }
+
+    pub fn get_surface_elevation(&self, x: f32, y: f32) -> Option<f32> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let from = v2(x.floor() as usize, y.floor() as usize);
+        let to = v2(
+            (from.x + 1).min(self.width - 1),
+            (from.y + 1).min(self.height - 1),
+        );
+        if !self.in_bounds(&from) {
+            return None;
+        }
+        let elevations = self.terrain.elevations();
+        let fx = x - from.x as f32;
+        let fy = y - from.y as f32;
+        let top = elevations[(from.x, from.y)] * (1.0 - fx) + elevations[(to.x, from.y)] * fx;
+        let bottom = elevations[(from.x, to.y)] * (1.0 - fx) + elevations[(to.x, to.y)] * fx;
+        Some(top * (1.0 - fy) + bottom * fy)
+    }
+
     pub fn get_rise(&self, edge: &Edge) -> Option<f32> {
         match (
             self.get_elevation(edge.from()),
@@ -237,6 +681,7 @@ impl World {
 mod tests {
 
     use super::*;
+    use crate::lakes::get_lakes;
 
     #[rustfmt::skip]
     fn world() -> World {
@@ -258,10 +703,22 @@ mod tests {
                 Edge::new(v2(1, 1), v2(1, 2)),
                 Edge::new(v2(1, 2), v2(2, 2)),
             ],
+            vec![],
             0.5
         )
     }
 
+    #[rustfmt::skip]
+    fn world_with_lake() -> World {
+        let elevations = M::from_vec(3, 3, vec![
+            2.0, 2.0, 2.0,
+            2.0, 1.0, 2.0,
+            2.0, 2.0, 2.0,
+        ]);
+        let lakes = get_lakes(&elevations, 0.5);
+        World::new(elevations, vec![], vec![], lakes, 0.5)
+    }
+
     #[test]
     fn test_terrain() {
         let terrain = world().terrain;
@@ -307,7 +764,7 @@ mod tests {
         assert!(!world.terrain.is_edge(&Edge::new(v2(0, 1), v2(1, 1))));
 
         world.add_road(&Edge::new(v2(0, 0), v2(0, 1)));
-        world.toggle_road(&Edge::new(v2(0, 1), v2(1, 1)));
+        assert!(world.toggle_road(&Edge::new(v2(0, 1), v2(1, 1)), Owner::Player).is_some());
 
         let after_widths = M::from_vec(3, 3, vec![
             World::ROAD_WIDTH, 0.1, 0.0,
@@ -337,7 +794,7 @@ mod tests {
         assert!(world.terrain.is_edge(&Edge::new(v2(0, 1), v2(1, 1))));
 
         world.clear_road(&Edge::new(v2(0, 0), v2(0, 1)));
-        world.toggle_road(&Edge::new(v2(0, 1), v2(1, 1)));
+        assert!(world.toggle_road(&Edge::new(v2(0, 1), v2(1, 1)), Owner::Player).is_some());
 
         for x in 0..3 {
             for y in 0..3 {
@@ -356,6 +813,56 @@ mod tests {
         assert!(!world.terrain.is_edge(&Edge::new(v2(0, 1), v2(1, 1))));
     }
 
+    #[test]
+    fn test_can_build_road() {
+        let mut world = world();
+        let edge = Edge::new(v2(0, 0), v2(0, 1));
+        assert!(world.can_build_road(&edge, Owner::Player));
+        world.set_sea_level(1.5);
+        assert!(!world.can_build_road(&edge, Owner::Player));
+        let world = world_with_lake();
+        assert!(!world.can_build_road(&Edge::new(v2(0, 1), v2(1, 1)), Owner::Player));
+        assert!(world.can_build_road(&Edge::new(v2(0, 0), v2(0, 1)), Owner::Player));
+    }
+
+    #[test]
+    fn test_toggle_road_checks_territory() {
+        let mut world = world();
+        let edge = Edge::new(v2(0, 0), v2(0, 1));
+        let settlement = world.territory_mut().new_settlement();
+        world.territory_mut().claim(&v2(0, 0), 1, settlement);
+        assert!(world.toggle_road(&edge, Owner::Player).is_none());
+        assert!(!world.roads().is_road(&edge));
+        assert!(world.toggle_road(&edge, settlement).is_some());
+        assert!(world.roads().is_road(&edge));
+    }
+
+    #[test]
+    fn test_wear_roads_does_not_upgrade_tracks_into_the_sea() {
+        let mut world = world();
+        world.set_sea_level(1.5);
+        for _ in 0..4 {
+            world.record_traffic(&v2(0, 0), &v2(0, 1), 4.0, Owner::Player);
+            assert!(world.wear_roads().is_empty());
+        }
+        assert!(!world.roads().is_road(&Edge::new(v2(0, 0), v2(0, 1))));
+    }
+
+    #[test]
+    fn test_worn_road_releases_owner_claim() {
+        let mut world = world();
+        let edge = Edge::new(v2(0, 0), v2(0, 1));
+        let settlement = world.territory_mut().new_settlement();
+        world.record_traffic(&v2(0, 0), &v2(0, 1), 12.0, settlement);
+        assert!(!world.wear_roads().is_empty());
+        assert!(world.roads().is_road(&edge));
+        assert_eq!(world.territory().get(&v2(0, 0)), Some(settlement));
+        while world.roads().is_road(&edge) {
+            world.wear_roads();
+        }
+        assert_eq!(world.territory().get(&v2(0, 0)), None);
+    }
+
     #[test]
     fn test_snap() {
         assert_eq!(
@@ -406,6 +913,181 @@ mod tests {
         assert_eq!(world().get_elevation(&v2(1, 1)).unwrap(), 2.0);
     }
 
+    #[test]
+    fn test_set_sea_level() {
+        let mut world = world();
+        assert!(!world.is_sea(&v2(0, 0)));
+        world.set_sea_level(1.5);
+        assert!(world.is_sea(&v2(0, 0)));
+        assert!(!world.is_sea(&v2(1, 1)));
+    }
+
+    #[test]
+    fn test_get_lake_level() {
+        let world = world_with_lake();
+        assert_eq!(world.get_lake_level(&v2(1, 1)), Some(2.0));
+        assert_eq!(world.get_lake_level(&v2(0, 1)), None);
+        assert_eq!(world.get_lake_level(&v2(3, 3)), None);
+    }
+
+    #[test]
+    fn test_is_lake() {
+        assert!(world_with_lake().is_lake(&v2(0, 0)));
+        assert!(world_with_lake().is_lake(&v2(1, 1)));
+        assert!(!world().is_lake(&v2(0, 0)));
+    }
+
+    #[test]
+    fn test_explore() {
+        let mut world = world();
+        assert!(!world.is_explored(&v2(0, 0)));
+        assert!(!world.is_tile_explored(&v2(0, 0)));
+        assert!(world.explore(&v2(1, 1)));
+        assert!(!world.explore(&v2(1, 1)));
+        assert!(!world.explore(&v2(3, 3)));
+        assert!(world.is_explored(&v2(1, 1)));
+        assert!(world.is_tile_explored(&v2(0, 0)));
+        assert!(world.is_tile_explored(&v2(1, 1)));
+    }
+
+    #[rustfmt::skip]
+    fn ridge() -> World {
+        World::new(
+            M::from_vec(5, 1, vec![
+                1.0, 1.0, 2.0, 1.0, 3.0,
+            ]),
+            vec![],
+            vec![],
+            vec![],
+            0.5,
+        )
+    }
+
+    #[test]
+    fn test_viewshed() {
+        let viewshed = ridge().viewshed(&WorldCoord::new(0.0, 0.0, 1.0), 0.0, 10);
+        assert!(viewshed.contains(&v2(0, 0)));
+        assert!(viewshed.contains(&v2(1, 0)));
+        assert!(viewshed.contains(&v2(2, 0)));
+        assert!(!viewshed.contains(&v2(3, 0)));
+        assert!(viewshed.contains(&v2(4, 0)));
+    }
+
+    #[test]
+    fn test_viewshed_eye_height() {
+        let viewshed = ridge().viewshed(&WorldCoord::new(0.0, 0.0, 1.0), 4.0, 10);
+        assert_eq!(viewshed.len(), 5);
+    }
+
+    #[test]
+    fn test_viewshed_radius() {
+        let viewshed = ridge().viewshed(&WorldCoord::new(0.0, 0.0, 1.0), 0.0, 2);
+        assert!(viewshed.contains(&v2(2, 0)));
+        assert!(!viewshed.contains(&v2(4, 0)));
+    }
+
+    #[test]
+    fn test_get_river_width() {
+        assert_eq!(
+            world().get_river_width(&Edge::new(v2(1, 0), v2(1, 1))),
+            Some(0.2)
+        );
+        assert_eq!(
+            world().get_river_width(&Edge::new(v2(1, 2), v2(2, 2))),
+            Some(0.4)
+        );
+        assert_eq!(
+            world().get_river_width(&Edge::new(v2(0, 0), v2(1, 0))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_widen_rivers() {
+        let mut world = world();
+        let edge = Edge::new(v2(1, 2), v2(2, 2));
+        assert_eq!(world.widen_rivers(2.0, 0.7).len(), 5);
+        assert_eq!(world.get_river_width(&edge), Some(0.7));
+        assert_eq!(
+            world.get_river_width(&Edge::new(v2(1, 0), v2(1, 1))),
+            Some(0.4)
+        );
+    }
+
+    #[test]
+    fn test_version_changes_with_routes() {
+        let mut world = world();
+        let version = world.version();
+        world.add_road(&Edge::new(v2(0, 0), v2(1, 0)));
+        assert!(world.version() > version);
+        let version = world.version();
+        world.set_sea_level(0.6);
+        assert!(world.version() > version);
+        let version = world.version();
+        world.set_river_swell(2.0);
+        assert!(world.version() > version);
+    }
+
+    #[test]
+    fn test_set_elevations() {
+        let mut world = world();
+        world.add_road(&Edge::new(v2(0, 0), v2(1, 0)));
+        world.set_elevations(M::from_element(3, 3, 0.75));
+        assert_eq!(world.get_elevation(&v2(1, 1)), Some(0.75));
+        assert_eq!(world.max_height(), 0.75);
+        assert!(world.terrain.is_edge(&Edge::new(v2(0, 0), v2(1, 0))));
+        assert_eq!(world.terrain.get_node(v2(1, 1)).width(), 0.2);
+    }
+
+    #[test]
+    fn test_set_elevations_updates_lakes() {
+        let mut world = world_with_lake();
+        assert!(world.get_lake_level(&v2(1, 1)).is_some());
+        assert_eq!(
+            world.set_elevations(M::from_element(3, 3, 2.0)),
+            vec![v2(1, 1)]
+        );
+        assert!(world.lakes().is_empty());
+        assert_eq!(world.get_lake_level(&v2(1, 1)), None);
+        assert!(world.set_elevations(M::from_element(3, 3, 2.0)).is_empty());
+    }
+
+    #[test]
+    fn test_set_river_swell() {
+        let mut world = world();
+        let edge = Edge::new(v2(1, 2), v2(2, 2));
+        assert_eq!(world.set_river_swell(2.0).len(), 5);
+        assert_eq!(world.get_river_width(&edge), Some(0.8));
+        assert!(world.set_river_swell(2.0).is_empty());
+        world.set_river_swell(1.0);
+        assert_eq!(world.get_river_width(&edge), Some(0.4));
+    }
+
+    #[test]
+    fn test_get_road_edges_straight() {
+        assert_eq!(
+            world().get_road_edges(&v2(0, 0), &v2(1, 0)),
+            vec![Edge::new(v2(0, 0), v2(1, 0))]
+        );
+    }
+
+    #[test]
+    fn test_get_road_edges_diagonal_avoids_hill() {
+        assert_eq!(
+            world().get_road_edges(&v2(0, 1), &v2(1, 2)),
+            vec![Edge::new(v2(0, 1), v2(0, 2)), Edge::new(v2(0, 2), v2(1, 2))]
+        );
+    }
+
+    #[test]
+    fn test_get_surface_elevation() {
+        assert_eq!(world().get_surface_elevation(1.0, 1.0), Some(2.0));
+        assert_eq!(world().get_surface_elevation(0.5, 1.0), Some(1.5));
+        assert_eq!(world().get_surface_elevation(0.5, 0.5), Some(1.25));
+        assert_eq!(world().get_surface_elevation(2.0, 2.0), Some(1.0));
+        assert_eq!(world().get_surface_elevation(3.0, 0.0), None);
+    }
+
     #[test]
     fn test_get_rise() {
         assert_eq!(
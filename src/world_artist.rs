@@ -1,8 +1,13 @@
+use crate::contours::get_contour_edges;
+use crate::flood::Floods;
+use crate::lakes::Lake;
+use crate::overlay::{get_viewshed_color, get_viewshed_colors, Overlay};
+use crate::palette::Palette;
 use crate::world::World;
 use isometric::drawing::*;
 use isometric::terrain::*;
 use isometric::*;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
 #[derive(Hash, PartialEq, Eq, Debug)]
 struct Slab {
@@ -21,63 +26,318 @@ impl Slab {
     }
 }
 
+const ELEVATION_BUCKET: f32 = 0.1;
+
+fn get_bucket(elevation: f32) -> i64 {
+    (elevation / ELEVATION_BUCKET).floor() as i64
+}
+
+struct TileColors {
+    colors: M<Color>,
+    lowest: M<f32>,
+    buckets: BTreeMap<i64, HashSet<V2<usize>>>,
+    cliff_gradient: f32,
+    beach_height: f32,
+    sea_level: f32,
+}
+
+impl TileColors {
+    fn new(world: &World, cliff_gradient: f32, beach_height: f32, palette: &Palette) -> TileColors {
+        let (width, height) = world.terrain().elevations().shape();
+        let mut out = TileColors {
+            colors: M::from_element(width - 1, height - 1, palette.unexplored),
+            lowest: M::from_fn(width - 1, height - 1, |x, y| {
+                world.get_lowest_corner(&v2(x, y))
+            }),
+            buckets: BTreeMap::new(),
+            cliff_gradient,
+            beach_height,
+            sea_level: world.sea_level(),
+        };
+        for position in out.all() {
+            let bucket = get_bucket(out.lowest[(position.x, position.y)]);
+            out.buckets.entry(bucket).or_default().insert(position);
+        }
+        out.recolor_all(world, palette);
+        out
+    }
+
+    fn reindex(&mut self, world: &World, position: &V2<usize>) {
+        let index = (position.x, position.y);
+        let lowest = world.get_lowest_corner(position);
+        let (from, to) = (get_bucket(self.lowest[index]), get_bucket(lowest));
+        self.lowest[index] = lowest;
+        if from != to {
+            self.buckets.get_mut(&from).unwrap().remove(position);
+            self.buckets.entry(to).or_default().insert(*position);
+        }
+    }
+
+    fn get_color(&self, world: &World, position: &V2<usize>, palette: &Palette) -> Color {
+        let beach_level = world.sea_level() + self.beach_height;
+        let max_gradient = world.get_max_abs_rise(position);
+        let min_elevation = world.get_lowest_corner(position);
+        if !world.is_tile_explored(position) {
+            palette.unexplored
+        } else if world.get_snow(position) > 0.0 {
+            palette.snow
+        } else if max_gradient > self.cliff_gradient {
+            palette.cliff
+        } else if world.is_lake(position) {
+            palette.lake
+        } else if world.is_flooded(position) {
+            palette.river
+        } else if world.is_farmland(position) {
+            palette.farmland
+        } else if min_elevation < beach_level {
+            palette.beach
+        } else {
+            world.season().vegetation(&palette.grass)
+        }
+    }
+
+    fn in_bounds(&self, position: &V2<usize>) -> bool {
+        let (width, height) = self.colors.shape();
+        position.x < width && position.y < height
+    }
+
+    fn update(&mut self, world: &World, position: V2<usize>, palette: &Palette) -> bool {
+        self.reindex(world, &position);
+        let color = self.get_color(world, &position, palette);
+        let index = (position.x, position.y);
+        if self.colors[index] != color {
+            self.colors[index] = color;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn update_all<I>(&mut self, world: &World, positions: I, palette: &Palette) -> Vec<V2<usize>>
+    where
+        I: IntoIterator<Item = V2<usize>>,
+    {
+        self.sea_level = world.sea_level();
+        positions
+            .into_iter()
+            .filter(|position| self.update(world, *position, palette))
+            .collect()
+    }
+
+    fn expand(&self, world: &World, positions: &[V2<usize>]) -> HashSet<V2<usize>> {
+        positions
+            .iter()
+            .flat_map(|position| world.expand_position(position))
+            .filter(|position| self.in_bounds(position))
+            .collect()
+    }
+
+    fn recolor(
+        &mut self,
+        world: &World,
+        positions: &[V2<usize>],
+        palette: &Palette,
+    ) -> Vec<V2<usize>> {
+        let affected = self.expand(world, positions);
+        self.update_all(world, affected, palette)
+    }
+
+    fn all(&self) -> Vec<V2<usize>> {
+        let (width, height) = self.colors.shape();
+        (0..width)
+            .flat_map(|x| (0..height).map(move |y| v2(x, y)))
+            .collect()
+    }
+
+    fn recolor_all(&mut self, world: &World, palette: &Palette) -> Vec<V2<usize>> {
+        self.update_all(world, self.all(), palette)
+    }
+
+    fn get_tiles_between(&self, from: f32, to: f32) -> Vec<V2<usize>> {
+        self.buckets
+            .range(get_bucket(from)..=get_bucket(to))
+            .flat_map(|(_, positions)| positions.iter())
+            .filter(|position| {
+                let lowest = self.lowest[(position.x, position.y)];
+                lowest >= from && lowest < to
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn recolor_sea_level(&mut self, world: &World, palette: &Palette) -> Vec<V2<usize>> {
+        let from = self.sea_level.min(world.sea_level()) + self.beach_height;
+        let to = self.sea_level.max(world.sea_level()) + self.beach_height;
+        let affected = self.get_tiles_between(from, to);
+        self.update_all(world, affected, palette)
+    }
+}
+
+const LAKE_DEPTH: f32 = 1.0;
+const LAKE_OUTLET_WIDTH: f32 = 0.1;
+
+fn get_lake_surface(world: &World, shown: &[bool]) -> Terrain {
+    let mut elevations = world.terrain().elevations().map(|z| z - LAKE_DEPTH);
+    for (lake, shown) in world.lakes().iter().zip(shown) {
+        if *shown {
+            for position in lake.positions() {
+                elevations[(position.x, position.y)] = lake.level();
+            }
+        }
+    }
+    Terrain::new(elevations, &vec![], &vec![])
+}
+
+fn get_sea_elevation(world: &World, position: &V2<usize>) -> f32 {
+    let elevation = world.terrain().elevations()[(position.x, position.y)];
+    if elevation < world.sea_level() && world.is_explored(position) {
+        world.sea_level()
+    } else {
+        elevation - LAKE_DEPTH
+    }
+}
+
+fn get_sea_surface(world: &World) -> Terrain {
+    let (width, height) = world.terrain().elevations().shape();
+    let surface = M::from_fn(width, height, |x, y| get_sea_elevation(world, &v2(x, y)));
+    Terrain::new(surface, &vec![], &vec![])
+}
+
+fn get_lake_extents(
+    world: &World,
+    width: usize,
+    height: usize,
+) -> Vec<(f32, V2<usize>, V2<usize>)> {
+    world
+        .lakes()
+        .iter()
+        .map(|lake| {
+            let (from, to) = get_lake_bounds(lake, width, height);
+            (lake.level(), from, to)
+        })
+        .collect()
+}
+
+fn get_lake_bounds(lake: &Lake, width: usize, height: usize) -> (V2<usize>, V2<usize>) {
+    let positions = lake.positions();
+    let min_x = positions.iter().map(|position| position.x).min().unwrap();
+    let min_y = positions.iter().map(|position| position.y).min().unwrap();
+    let max_x = positions.iter().map(|position| position.x).max().unwrap();
+    let max_y = positions.iter().map(|position| position.y).max().unwrap();
+    (
+        v2(min_x.saturating_sub(1), min_y.saturating_sub(1)),
+        v2((max_x + 1).min(width - 1), (max_y + 1).min(height - 1)),
+    )
+}
+
+#[derive(Clone, Copy)]
+pub struct TerrainStyle {
+    pub cliff_gradient: f32,
+    pub beach_height: f32,
+    pub contour_interval: f32,
+}
+
 pub struct WorldArtist {
     width: usize,
     height: usize,
     drawing: TerrainDrawing,
-    colors: M<Color>,
+    lake_drawing: TerrainDrawing,
+    sea_drawing: TerrainDrawing,
+    sea_surface: Terrain,
+    sea_colors: M<Color>,
+    lake_surface: Terrain,
+    lake_colors: M<Color>,
+    lakes_shown: Vec<bool>,
+    lakes_drawn: Vec<(f32, V2<usize>, V2<usize>)>,
+    lakes_stale: bool,
+    colors: TileColors,
     shading: Box<SquareColoring>,
     slab_size: usize,
+    floods: Floods,
+    overlay: Overlay,
+    viewshed: Option<HashSet<V2<usize>>>,
+    overlay_colors: Option<M<Color>>,
+    contour_interval: f32,
+    show_contours: bool,
+    palette: Palette,
+    pending: Vec<Slab>,
+    version: u64,
 }
 
 impl WorldArtist {
     pub fn new(
         world: &World,
         slab_size: usize,
-        cliff_gradient: f32,
-        beach_level: f32,
+        style: TerrainStyle,
+        floods: Floods,
         light_direction: V3<f32>,
+        palette: Palette,
     ) -> WorldArtist {
         let (width, height) = world.terrain().elevations().shape();
         WorldArtist {
             width,
             height,
             drawing: TerrainDrawing::new(width, height, slab_size),
-            colors: WorldArtist::get_colors(world, cliff_gradient, beach_level),
+            lake_drawing: TerrainDrawing::new(width, height, slab_size),
+            sea_drawing: TerrainDrawing::new(width, height, slab_size),
+            sea_surface: get_sea_surface(world),
+            sea_colors: M::from_element(width - 1, height - 1, palette.sea),
+            lake_surface: get_lake_surface(world, &vec![false; world.lakes().len()]),
+            lake_colors: M::from_element(width - 1, height - 1, palette.lake),
+            lakes_shown: vec![false; world.lakes().len()],
+            lakes_drawn: get_lake_extents(world, width, height),
+            lakes_stale: false,
+            colors: TileColors::new(world, style.cliff_gradient, style.beach_height, &palette),
             shading: WorldArtist::get_shading(light_direction),
             slab_size,
+            floods,
+            overlay: Overlay::None,
+            viewshed: None,
+            overlay_colors: None,
+            contour_interval: style.contour_interval,
+            show_contours: false,
+            palette,
+            pending: vec![],
+            version: 0,
         }
     }
 
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn colors(&self) -> &M<Color> {
+        &self.colors.colors
+    }
+
     fn get_shading(light_direction: V3<f32>) -> Box<SquareColoring> {
-        Box::new(AngleSquareColoring::new(
-            Color::new(1.0, 1.0, 1.0, 1.0),
-            light_direction,
-        ))
+        WorldArtist::get_tinted_shading(light_direction, Color::new(1.0, 1.0, 1.0, 1.0))
     }
 
-    fn get_colors(world: &World, cliff_gradient: f32, beach_level: f32) -> M<Color> {
-        let (width, height) = world.terrain().elevations().shape();
-        M::from_fn(width - 1, height - 1, |x, y| {
-            WorldArtist::get_color(world, &v2(x, y), cliff_gradient, beach_level)
-        })
+    fn get_tinted_shading(light_direction: V3<f32>, tint: Color) -> Box<dyn SquareColoring> {
+        Box::new(AngleSquareColoring::new(tint, light_direction))
     }
 
-    fn get_color(
-        world: &World,
-        position: &V2<usize>,
-        cliff_gradient: f32,
-        beach_level: f32,
-    ) -> Color {
-        let max_gradient = world.get_max_abs_rise(&position);
-        let min_elevation = world.get_lowest_corner(&position);
-        if max_gradient > cliff_gradient {
-            Color::new(0.5, 0.4, 0.3, 1.0)
-        } else if min_elevation < beach_level {
-            Color::new(1.0, 1.0, 0.0, 1.0)
-        } else {
-            Color::new(0.0, 0.75, 0.0, 1.0)
+    pub fn set_light(&mut self, light_direction: V3<f32>, tint: Color) {
+        self.shading = WorldArtist::get_tinted_shading(light_direction, tint);
+        self.pending = self.get_all_slabs().into_iter().collect();
+        self.lakes_stale = true;
+    }
+
+    pub fn draw_pending(&mut self, world: &World, max_slabs: usize) -> Vec<Command> {
+        if self.pending.is_empty() {
+            return vec![];
         }
+        let split = self.pending.len().saturating_sub(max_slabs);
+        for slab in self.pending.split_off(split) {
+            self.draw_slab_tiles(world, &slab);
+            self.draw_slab_sea(&slab);
+        }
+        let mut out = vec![self.draw_terrain(), self.draw_sea()];
+        if self.pending.is_empty() {
+            out.append(&mut self.draw_lakes(world));
+        }
+        out
     }
 
     pub fn draw_terrain(&self) -> Command {
@@ -87,27 +347,267 @@ impl WorldArtist {
         }
     }
 
-    pub fn draw_sea(&self, world: &World) -> Command {
+    fn draw_sea(&self) -> Command {
         Command::Draw {
             name: "sea".to_string(),
-            drawing: Box::new(SeaDrawing::new(
-                self.width as f32,
-                self.height as f32,
-                world.sea_level(),
-            )),
+            drawing: Box::new(self.sea_drawing.clone()),
+        }
+    }
+
+    fn draw_lakes(&mut self, world: &World) -> Vec<Command> {
+        let shown: Vec<bool> = world
+            .lakes()
+            .iter()
+            .map(|lake| {
+                lake.positions()
+                    .iter()
+                    .any(|position| world.is_explored(position))
+            })
+            .collect();
+        let drawn = get_lake_extents(world, self.width, self.height);
+        let moved = drawn != self.lakes_drawn;
+        if shown == self.lakes_shown && !self.lakes_stale && !moved {
+            return vec![];
         }
+        if moved || shown != self.lakes_shown {
+            self.lake_surface = get_lake_surface(world, &shown);
+        }
+        let surface = &self.lake_surface;
+        let colors = &self.lake_colors;
+        if moved {
+            for (_, from, to) in self.lakes_drawn.iter() {
+                self.lake_drawing
+                    .update(surface, colors, &self.shading, *from, *to);
+            }
+        }
+        for (index, lake) in world.lakes().iter().enumerate() {
+            if shown[index] && (self.lakes_stale || moved || !self.lakes_shown[index]) {
+                let (from, to) = get_lake_bounds(lake, self.width, self.height);
+                self.lake_drawing
+                    .update(surface, colors, &self.shading, from, to);
+            }
+        }
+        let outlets = world
+            .lakes()
+            .iter()
+            .zip(shown.iter())
+            .filter(|(_, shown)| **shown)
+            .map(|(lake, _)| Node::new(*lake.spill_point(), LAKE_OUTLET_WIDTH, LAKE_OUTLET_WIDTH))
+            .collect();
+        self.lakes_shown = shown;
+        self.lakes_drawn = drawn;
+        self.lakes_stale = false;
+        vec![
+            Command::Draw {
+                name: "lakes".to_string(),
+                drawing: Box::new(self.lake_drawing.clone()),
+            },
+            Command::Draw {
+                name: "lake-outlets".to_string(),
+                drawing: Box::new(NodeDrawing::new(
+                    world.terrain(),
+                    &outlets,
+                    &self.palette.river,
+                    0.0,
+                )),
+            },
+        ]
+    }
+
+    pub fn draw_sea_level_change(&mut self, world: &World) -> Vec<Command> {
+        self.sea_surface = get_sea_surface(world);
+        let from = self.colors.sea_level.min(world.sea_level());
+        let sea_level = self.colors.sea_level.max(world.sea_level());
+        let to = sea_level + self.colors.beach_height;
+        let mut recolored = self.colors.recolor_sea_level(world, &self.palette);
+        let band = self.colors.get_tiles_between(from, to);
+        recolored.append(&mut self.update_overlay(world, band));
+        let sea = self
+            .colors
+            .get_tiles_between(f32::MIN, sea_level)
+            .into_iter()
+            .filter(|position| world.is_tile_explored(position))
+            .collect();
+        let mut pending: HashSet<Slab> = self.pending.drain(..).collect();
+        pending.extend(self.get_affected_slabs(world, sea));
+        self.pending = pending.into_iter().collect();
+        self.draw_slabs(world, self.get_affected_slabs(world, recolored))
+    }
+
+    pub fn redraw_colors(&mut self, world: &World) -> Vec<Command> {
+        let mut recolored = self.colors.recolor_all(world, &self.palette);
+        recolored.append(&mut self.update_overlay(world, recolored.clone()));
+        if recolored.is_empty() {
+            return vec![];
+        }
+        self.draw_slabs(world, self.get_affected_slabs(world, recolored))
+    }
+
+    fn get_overlay_color(&self, world: &World, position: &V2<usize>) -> Option<Color> {
+        match &self.viewshed {
+            Some(viewshed) => {
+                let color = &self.colors.colors[(position.x, position.y)];
+                Some(get_viewshed_color(world, color, viewshed, position))
+            }
+            None => {
+                self.overlay
+                    .get_color(world, position, self.colors.cliff_gradient, &self.floods)
+            }
+        }
+    }
+
+    fn update_overlay<I>(&mut self, world: &World, positions: I) -> Vec<V2<usize>>
+    where
+        I: IntoIterator<Item = V2<usize>>,
+    {
+        if self.overlay_colors.is_none() {
+            return vec![];
+        }
+        let mut out = vec![];
+        for position in positions {
+            let color = self.get_overlay_color(world, &position).unwrap();
+            let colors = self.overlay_colors.as_mut().unwrap();
+            let index = (position.x, position.y);
+            if colors[index] != color {
+                colors[index] = color;
+                out.push(position);
+            }
+        }
+        out
+    }
+
+    fn recolor_overlay(&mut self, world: &World) {
+        self.overlay_colors = match &self.viewshed {
+            Some(viewshed) => Some(get_viewshed_colors(world, self.colors(), viewshed)),
+            None => self
+                .overlay
+                .get_colors(world, self.colors.cliff_gradient, &self.floods),
+        };
+    }
+
+    pub fn redraw_overlay(&mut self, world: &World) -> Vec<Command> {
+        let recolored = self.update_overlay(world, self.colors.all());
+        if recolored.is_empty() {
+            return vec![];
+        }
+        self.draw_slabs(world, self.get_affected_slabs(world, recolored))
+    }
+
+    pub fn cycle_overlay(&mut self, world: &World) -> Vec<Command> {
+        self.overlay = self.overlay.next();
+        self.viewshed = None;
+        self.recolor_overlay(world);
+        self.draw_all(world)
+    }
+
+    pub fn show_viewshed(
+        &mut self,
+        world: &World,
+        viewshed: Option<HashSet<V2<usize>>>,
+    ) -> Vec<Command> {
+        self.overlay = Overlay::None;
+        self.viewshed = viewshed;
+        self.recolor_overlay(world);
+        self.draw_all(world)
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn set_palette(&mut self, world: &World, palette: Palette) -> Vec<Command> {
+        self.palette = palette;
+        self.sea_colors = M::from_element(self.width - 1, self.height - 1, palette.sea);
+        self.lake_colors = M::from_element(self.width - 1, self.height - 1, palette.lake);
+        self.colors.recolor_all(world, &palette);
+        self.recolor_overlay(world);
+        self.lakes_stale = true;
+        let mut out = self.draw_all(world);
+        out.append(&mut self.draw_lakes(world));
+        out
+    }
+
+    pub fn toggle_contours(&mut self, world: &World) -> Vec<Command> {
+        self.show_contours = !self.show_contours;
+        self.draw_all(world)
     }
 
     fn draw_slab(&mut self, world: &World, slab: &Slab) -> Vec<Command> {
         self.draw_slab_tiles(world, slab);
-        self.draw_slab_rivers_roads(world, &slab)
+        self.draw_slab_sea(slab);
+        let mut out = self.draw_slab_rivers_roads(world, slab);
+        out.push(self.draw_slab_contours(world, slab));
+        out.push(self.draw_slab_borders(world, slab));
+        out
+    }
+
+    fn draw_slab_borders(&self, world: &World, slab: &Slab) -> Command {
+        let mut edges = world.territory().get_borders(&slab.from, &slab.to());
+        edges.retain(|edge| world.is_explored(edge.from()) && world.is_explored(edge.to()));
+        Command::Draw {
+            name: format!("{:?}-borders", slab.from),
+            drawing: Box::new(EdgeDrawing::new(
+                world.terrain(),
+                &edges,
+                &self.palette.border,
+                0.0,
+            )),
+        }
+    }
+
+    fn draw_slab_contours(&self, world: &World, slab: &Slab) -> Command {
+        let contour_color = &self.palette.contour;
+        let mut edges = if self.show_contours {
+            get_contour_edges(
+                world.terrain().elevations(),
+                self.contour_interval,
+                world.max_height(),
+                &slab.from,
+                &slab.to(),
+            )
+        } else {
+            vec![]
+        };
+        edges.retain(|edge| world.is_explored(edge.from()) && world.is_explored(edge.to()));
+        Command::Draw {
+            name: format!("{:?}-contours", slab.from),
+            drawing: Box::new(EdgeDrawing::new(
+                world.terrain(),
+                &edges,
+                contour_color,
+                0.0,
+            )),
+        }
     }
 
     fn draw_slab_tiles(&mut self, world: &World, slab: &Slab) {
         let to = slab.to();
         let to = v2(to.x.min(self.width - 1), to.y.min(self.height - 1));
+        let colors = self.overlay_colors.as_ref().unwrap_or(&self.colors.colors);
         self.drawing
-            .update(world.terrain(), &self.colors, &self.shading, slab.from, to);
+            .update(world.terrain(), colors, &self.shading, slab.from, to);
+    }
+
+    fn draw_slab_sea(&mut self, slab: &Slab) {
+        let to = slab.to();
+        let to = v2(to.x.min(self.width - 1), to.y.min(self.height - 1));
+        self.sea_drawing.update(
+            &self.sea_surface,
+            &self.sea_colors,
+            &self.shading,
+            slab.from,
+            to,
+        );
+    }
+
+    fn update_sea_surface(&mut self, world: &World, positions: &[V2<usize>]) {
+        let surface = self.sea_surface.elevations();
+        if positions
+            .iter()
+            .any(|position| surface[(position.x, position.y)] != get_sea_elevation(world, position))
+        {
+            self.sea_surface = get_sea_surface(world);
+        }
     }
 
     fn get_road_river_nodes(
@@ -120,6 +620,9 @@ impl WorldArtist {
         let mut river_nodes = vec![];
         for x in from.x..to.x {
             for y in from.y..to.y {
+                if !world.is_explored(&v2(x, y)) {
+                    continue;
+                }
                 let road_node = world.roads().get_node(v2(x, y));
                 let river_node = world.rivers().get_node(v2(x, y));
                 if road_node.width() > 0.0 || road_node.height() > 0.0 {
@@ -133,12 +636,15 @@ impl WorldArtist {
     }
 
     fn draw_slab_rivers_roads(&mut self, world: &World, slab: &Slab) -> Vec<Command> {
-        let river_color = &Color::new(0.0, 0.0, 1.0, 1.0);
-        let road_color = &Color::new(0.5, 0.5, 0.5, 1.0);
+        let river_color = &self.palette.river;
+        let road_color = &self.palette.road;
         let from = &slab.from;
         let to = &slab.to();
-        let river_edges = world.rivers().get_edges(from, to);
-        let road_edges = world.roads().get_edges(from, to);
+        let explored = |edge: &Edge| world.is_explored(edge.from()) && world.is_explored(edge.to());
+        let mut river_edges = world.rivers().get_edges(from, to);
+        river_edges.retain(explored);
+        let mut road_edges = world.roads().get_edges(from, to);
+        road_edges.retain(explored);
         let (road_nodes, river_nodes) = self.get_road_river_nodes(world, from, to);
         vec![
             Command::Draw {
@@ -181,11 +687,13 @@ impl WorldArtist {
     }
 
     fn draw_slabs(&mut self, world: &World, slabs: HashSet<Slab>) -> Vec<Command> {
+        self.version += 1;
         let mut out = vec![];
         for slab in slabs {
             out.append(&mut self.draw_slab(world, &slab));
         }
         out.push(self.draw_terrain());
+        out.push(self.draw_sea());
         out
     }
 
@@ -198,7 +706,15 @@ impl WorldArtist {
     }
 
     pub fn draw_affected(&mut self, world: &World, positions: Vec<V2<usize>>) -> Vec<Command> {
-        self.draw_slabs(world, self.get_affected_slabs(world, positions))
+        if positions.is_empty() {
+            return vec![];
+        }
+        self.colors.recolor(world, &positions, &self.palette);
+        self.update_sea_surface(world, &positions);
+        self.update_overlay(world, self.colors.expand(world, &positions));
+        let mut out = self.draw_slabs(world, self.get_affected_slabs(world, positions));
+        out.append(&mut self.draw_lakes(world));
+        out
     }
 
     fn get_all_slabs(&self) -> HashSet<Slab> {
@@ -219,8 +735,8 @@ impl WorldArtist {
     pub fn init(&mut self, world: &World) -> Vec<Command> {
         let mut out = vec![];
         out.push(self.draw_terrain());
-        out.push(self.draw_sea(world));
         out.append(&mut self.draw_all(world));
+        out.append(&mut self.draw_lakes(world));
         out
     }
 }
@@ -229,6 +745,231 @@ impl WorldArtist {
 mod tests {
 
     use super::*;
+    use crate::lakes::get_lakes;
+    use crate::territory::Owner;
+
+    #[rustfmt::skip]
+    fn world() -> World {
+        let mut world = World::new(
+            M::from_vec(4, 4, vec![
+                1.0, 1.0, 1.0, 1.0,
+                1.0, 1.0, 1.0, 1.0,
+                2.0, 2.0, 2.0, 2.0,
+                2.0, 2.0, 2.0, 2.0,
+            ]),
+            vec![],
+            vec![],
+            vec![],
+            0.5
+        );
+        world.set_explored(M::from_element(4, 4, true));
+        world
+    }
+
+    #[test]
+    fn test_recolor_after_terrain_change() {
+        let palette = Palette::standard();
+        let mut world = world();
+        let mut colors = TileColors::new(&world, 1.5, 0.2, &palette);
+        let grass = world.season().vegetation(&palette.grass);
+        assert_eq!(colors.colors[(0, 0)], grass);
+        let mut elevations = world.terrain().elevations().clone();
+        elevations[(1, 1)] = 3.0;
+        world.set_elevations(elevations);
+        let mut recolored = colors.recolor(&world, &[v2(1, 1)], &palette);
+        recolored.sort_by_key(|position| (position.x, position.y));
+        assert_eq!(recolored, vec![v2(0, 0), v2(0, 1), v2(1, 0), v2(1, 1)]);
+        assert_eq!(colors.colors[(1, 1)], palette.cliff);
+        assert_eq!(colors.colors[(2, 2)], grass);
+    }
+
+    #[test]
+    fn test_recolor_after_sea_level_change() {
+        let palette = Palette::standard();
+        let mut world = world();
+        let mut colors = TileColors::new(&world, 1.5, 0.2, &palette);
+        let grass = world.season().vegetation(&palette.grass);
+        world.set_sea_level(1.0);
+        assert_eq!(colors.recolor_sea_level(&world, &palette).len(), 6);
+        assert_eq!(colors.colors[(0, 1)], palette.beach);
+        assert_eq!(colors.colors[(0, 2)], grass);
+        world.set_sea_level(0.5);
+        assert_eq!(colors.recolor_sea_level(&world, &palette).len(), 6);
+        assert_eq!(colors.colors[(0, 1)], grass);
+    }
+
+    #[test]
+    fn test_tiles_between_follow_terrain_changes() {
+        let palette = Palette::standard();
+        let mut world = world();
+        let mut colors = TileColors::new(&world, 1.5, 0.2, &palette);
+        let mut tiles = colors.get_tiles_between(1.0, 1.5);
+        tiles.sort_by_key(|position| (position.x, position.y));
+        assert_eq!(
+            tiles,
+            vec![v2(0, 0), v2(0, 1), v2(1, 0), v2(1, 1), v2(2, 0), v2(2, 1)]
+        );
+        let mut elevations = world.terrain().elevations().clone();
+        elevations[(3, 2)] = 1.2;
+        world.set_elevations(elevations);
+        colors.recolor(&world, &[v2(3, 2)], &palette);
+        assert_eq!(colors.get_tiles_between(1.1, 1.5), vec![v2(2, 2)]);
+        assert!(!colors.get_tiles_between(2.0, 3.0).contains(&v2(2, 2)));
+    }
+
+    #[test]
+    fn test_recolor_only_affects_nearby_tiles() {
+        let palette = Palette::standard();
+        let mut world = world();
+        let mut colors = TileColors::new(&world, 1.5, 0.2, &palette);
+        world.set_farmland(&v2(0, 0), true);
+        world.set_farmland(&v2(2, 2), true);
+        let recolored = colors.recolor(&world, &[v2(0, 0)], &palette);
+        assert_eq!(recolored, vec![v2(0, 0)]);
+        assert_eq!(colors.colors[(0, 0)], palette.farmland);
+        assert_ne!(colors.colors[(2, 2)], palette.farmland);
+    }
+
+    fn artist(world: &World) -> WorldArtist {
+        let light = v3(0.0, 0.0, 1.0);
+        let style = TerrainStyle {
+            cliff_gradient: 1.5,
+            beach_height: 0.2,
+            contour_interval: 1.0,
+        };
+        let floods = Floods::new(1.0, 0.5, 5.0);
+        WorldArtist::new(world, 2, style, floods, light, Palette::standard())
+    }
+
+    #[test]
+    fn test_overlay_recolored_after_sea_level_change() {
+        let mut world = world();
+        let mut artist = artist(&world);
+        artist.cycle_overlay(&world);
+        let blue = Color::new(0.0, 0.0, 1.0, 1.0);
+        assert_ne!(artist.overlay_colors.as_ref().unwrap()[(0, 0)], blue);
+        world.set_sea_level(1.5);
+        artist.draw_sea_level_change(&world);
+        assert_eq!(artist.overlay_colors.as_ref().unwrap()[(0, 0)], blue);
+        assert_ne!(artist.overlay_colors.as_ref().unwrap()[(0, 2)], blue);
+    }
+
+    #[test]
+    fn test_sea_surface_only_over_explored_corners() {
+        let mut world = world();
+        world.set_sea_level(1.5);
+        let mut explored = M::from_element(4, 4, false);
+        explored[(0, 0)] = true;
+        world.set_explored(explored);
+        let sea = get_sea_surface(&world);
+        assert_eq!(sea.elevations()[(0, 0)], 1.5);
+        assert_eq!(sea.elevations()[(1, 0)], 1.0 - LAKE_DEPTH);
+        assert_eq!(sea.elevations()[(0, 2)], 2.0 - LAKE_DEPTH);
+    }
+
+    #[test]
+    fn test_sea_surface_follows_exploration() {
+        let mut world = world();
+        world.set_sea_level(1.5);
+        world.set_explored(M::from_element(4, 4, false));
+        let mut artist = artist(&world);
+        assert_eq!(artist.sea_surface.elevations()[(0, 0)], 1.0 - LAKE_DEPTH);
+        let mut explored = M::from_element(4, 4, false);
+        explored[(0, 0)] = true;
+        world.set_explored(explored);
+        artist.draw_affected(&world, vec![v2(0, 2)]);
+        assert_eq!(artist.sea_surface.elevations()[(0, 0)], 1.0 - LAKE_DEPTH);
+        artist.draw_affected(&world, vec![v2(0, 0)]);
+        assert_eq!(artist.sea_surface.elevations()[(0, 0)], 1.5);
+    }
+
+    #[test]
+    fn test_sea_redrawn_after_sea_level_change() {
+        let mut world = world();
+        let mut artist = artist(&world);
+        world.set_sea_level(1.5);
+        artist.draw_sea_level_change(&world);
+        assert!(!artist.pending.is_empty());
+        assert!(!artist.draw_pending(&world, 4).is_empty());
+        assert!(artist.pending.is_empty());
+    }
+
+    #[test]
+    fn test_overlay_recolored_after_terrain_change() {
+        let mut world = world();
+        let mut artist = artist(&world);
+        artist.cycle_overlay(&world);
+        artist.cycle_overlay(&world);
+        let flat = Color::new(0.0, 1.0, 0.0, 1.0);
+        let steep = Color::new(1.0, 0.0, 0.0, 1.0);
+        assert_eq!(artist.overlay_colors.as_ref().unwrap()[(0, 0)], flat);
+        let mut elevations = world.terrain().elevations().clone();
+        elevations[(1, 1)] = 2.5;
+        world.set_elevations(elevations);
+        artist.draw_affected(&world, vec![v2(1, 1)]);
+        assert_eq!(artist.overlay_colors.as_ref().unwrap()[(0, 0)], steep);
+        assert_eq!(artist.overlay_colors.as_ref().unwrap()[(2, 0)], flat);
+    }
+
+    #[test]
+    fn test_redraw_overlay_after_traffic() {
+        let mut world = world();
+        let mut artist = artist(&world);
+        while artist.overlay != Overlay::Traffic {
+            artist.cycle_overlay(&world);
+        }
+        let quiet = Color::new(0.5, 0.5, 0.5, 1.0);
+        assert_eq!(artist.overlay_colors.as_ref().unwrap()[(0, 0)], quiet);
+        world.record_traffic(&v2(0, 0), &v2(1, 0), 8.0, Owner::Player);
+        assert!(!artist.redraw_overlay(&world).is_empty());
+        assert_ne!(artist.overlay_colors.as_ref().unwrap()[(0, 0)], quiet);
+        assert!(artist.redraw_overlay(&world).is_empty());
+    }
+
+    #[rustfmt::skip]
+    fn world_with_lake() -> World {
+        let elevations = M::from_vec(4, 4, vec![
+            2.0, 2.0, 2.0, 2.0,
+            2.0, 1.0, 2.0, 2.0,
+            2.0, 2.0, 2.0, 2.0,
+            2.0, 2.0, 2.0, 2.0,
+        ]);
+        let lakes = get_lakes(&elevations, 0.5);
+        World::new(elevations, vec![], vec![], lakes, 0.5)
+    }
+
+    #[test]
+    fn test_lake_surface() {
+        let world = world_with_lake();
+        let surface = get_lake_surface(&world, &[true]);
+        assert_eq!(surface.elevations()[(1, 1)], 2.0);
+        assert_eq!(surface.elevations()[(0, 0)], 1.0);
+        let surface = get_lake_surface(&world, &[false]);
+        assert_eq!(surface.elevations()[(1, 1)], 0.0);
+    }
+
+    #[test]
+    fn test_lake_bounds() {
+        let world = world_with_lake();
+        assert_eq!(
+            get_lake_bounds(&world.lakes()[0], 4, 4),
+            (v2(0, 0), v2(2, 2))
+        );
+    }
+
+    #[test]
+    fn test_lakes_redrawn_when_lakes_change() {
+        let mut world = world_with_lake();
+        world.set_explored(M::from_element(4, 4, true));
+        let mut artist = artist(&world);
+        assert!(!artist.draw_lakes(&world).is_empty());
+        assert!(artist.draw_lakes(&world).is_empty());
+        let mut elevations = M::from_element(4, 4, 1.5);
+        elevations[(1, 1)] = 1.0;
+        world.set_elevations(elevations);
+        assert!(!artist.draw_lakes(&world).is_empty());
+        assert_eq!(artist.lakes_drawn[0].0, 1.5);
+    }
 
     #[test]
     fn slab_new() {
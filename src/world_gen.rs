@@ -1,3 +1,5 @@
+use crate::lakes::get_lakes;
+use crate::resources::get_resources;
 use crate::world::World;
 use pioneer::erosion::Erosion;
 use pioneer::mesh::Mesh;
@@ -7,7 +9,7 @@ use pioneer::river_runner::get_junctions_and_rivers;
 use pioneer::scale::Scale;
 use std::f64::MAX;
 
-pub fn generate_world(size: usize, seed: u8) -> World {
+pub fn generate_world(size: usize, seed: u8, cliff_gradient: f32) -> World {
     let mut mesh = Mesh::new(1, 0.0);
     mesh.set_z(0, 0, MAX);
     let mut rng = Box::new(SmallRng::from_seed([seed; 16]));
@@ -34,6 +36,10 @@ pub fn generate_world(size: usize, seed: u8) -> World {
         (0.0, max_height),
     ));
     let terrain = mesh.get_z_vector().map(|z| z as f32);
+    let lakes = get_lakes(&terrain, sea_level as f32);
 
-    World::new(terrain, junctions, rivers, sea_level as f32)
+    let mut world = World::new(terrain, junctions, rivers, lakes, sea_level as f32);
+    let resources = get_resources(&world, &mut rng, cliff_gradient);
+    world.set_resources(resources);
+    world
 }